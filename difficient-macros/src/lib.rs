@@ -1,59 +1,339 @@
 extern crate proc_macro;
 
 use darling::{
-    ast::{Data, Fields, Style},
-    FromDeriveInput, FromField, FromVariant,
+    ast::{Data, Fields, NestedMeta, Style},
+    util::{Flag, PathList},
+    FromDeriveInput, FromField, FromMeta, FromVariant,
 };
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote, ToTokens};
-use syn::{DeriveInput, Generics, Ident};
+use syn::{ext::IdentExt, spanned::Spanned, DeriveInput, Generics, Ident};
 
 #[derive(Debug, FromField)]
+#[darling(attributes(diffable))]
 struct StructLike {
     ident: Option<syn::Ident>,
     ty: syn::Type,
+    vis: syn::Visibility,
+    /// The field is itself a derived type, so its changed fields are reported as nested paths
+    #[darling(default)]
+    nested: bool,
+    /// Diff the field through a `remote` mirror type
+    with: Option<syn::Path>,
+    /// Diff the field by converting it into a proxy type
+    via: Option<syn::Path>,
+    /// Treat floats within this absolute difference as unchanged
+    epsilon: Option<f64>,
+    /// Treat floats within this difference, relative to their magnitude, as unchanged
+    relative: Option<f64>,
+    /// Treat floats within this many units in the last place as unchanged
+    ulps: Option<u64>,
+}
+
+impl StructLike {
+    /// The diff type of the field, at the given lifetime
+    fn diff_ty(&self, krate: &syn::Path, lifetime: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        match (&self.with, &self.via) {
+            (Some(with), _) => quote! { <#with as #krate::DiffableRemote<#lifetime>>::Diff },
+            (None, Some(via)) => quote! { #krate::ViaDiff<#lifetime, #ty, #via> },
+            (None, None) => quote! { <#ty as #krate::Diffable<#lifetime>>::Diff },
+        }
+    }
+
+    /// Diff the field, where `left` and `right` are references to the field on either side
+    fn diff_call(&self, krate: &syn::Path, left: TokenStream, right: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        match (&self.with, &self.via) {
+            (Some(with), _) => {
+                quote! { <#with as #krate::DiffableRemote<'a>>::diff(#left, #right) }
+            }
+            (None, Some(via)) => quote! { #krate::ViaDiff::<#ty, #via>::new(#left, #right) },
+            (None, None) if self.has_tolerance() => {
+                let tolerance = self.tolerance(krate);
+                quote! { #tolerance.diff(#left, #right) }
+            }
+            (None, None) => quote! { (#left).diff(#right) },
+        }
+    }
+
+    fn has_tolerance(&self) -> bool {
+        self.epsilon.is_some() || self.relative.is_some() || self.ulps.is_some()
+    }
+
+    /// The `Tolerance` given by the `epsilon`, `relative` and `ulps` attributes
+    fn tolerance(&self, krate: &syn::Path) -> TokenStream {
+        let absolute = self.epsilon.unwrap_or(0.0);
+        let relative = self.relative.unwrap_or(0.0);
+        let ulps = self.ulps.unwrap_or(0);
+        quote! {
+            #krate::tolerance::Tolerance {
+                absolute: #absolute,
+                relative: #relative,
+                ulps: #ulps,
+            }
+        }
+    }
+
+    /// Whether the field is equal on both sides, within its tolerance if it has one
+    fn eq_call(&self, krate: &syn::Path, left: TokenStream, right: TokenStream) -> TokenStream {
+        if self.has_tolerance() {
+            let tolerance = self.tolerance(krate);
+            quote! { #krate::tolerance::ApproxEq::approx_eq(#left, #right, &#tolerance) }
+        } else {
+            quote! { #left == #right }
+        }
+    }
 }
 
 #[derive(Debug, FromVariant)]
+#[darling(forward_attrs(non_exhaustive))]
 struct EnumData {
     ident: syn::Ident,
     fields: Fields<StructLike>,
+    attrs: Vec<syn::Attribute>,
 }
 
 #[derive(Debug, FromDeriveInput)]
+#[darling(attributes(diffable), forward_attrs(non_exhaustive))]
 struct DeriveDiffable {
     ident: syn::Ident,
     vis: syn::Visibility,
     data: Data<EnumData, StructLike>,
     generics: Generics,
+    attrs: Vec<syn::Attribute>,
+    /// Extra derives to add to the generated diff type
+    #[darling(default)]
+    diff_derive: PathList,
+    /// Extra attributes to add to the generated diff type
+    #[darling(default)]
+    diff_attr: MetaList,
+    /// Name of the generated diff type (default: `{Name}Diff`)
+    diff_name: Option<syn::Ident>,
+    /// Path to the `difficient` crate (default: `difficient`)
+    #[darling(rename = "crate")]
+    krate: Option<syn::Path>,
+    /// Diff this foreign type, treating the deriving type as a mirror of its definition
+    remote: Option<syn::Path>,
+    /// Whether to generate a `{Name}Field` enum and implement `ChangedFields` for the
+    /// diff type (default: on for structs with fields)
+    fields: Option<bool>,
+    /// Generate a `{Name}Mask` bitmask type and a `changed_mask` method
+    #[darling(default)]
+    mask: bool,
+    /// Check for equality with `PartialEq` before diffing field-by-field
+    eq_first: Flag,
+    /// Diff as a single value, replacing it whenever it changes
+    #[darling(default)]
+    atomic: bool,
+    /// Diff between different enum variants by reusing same-named fields
+    #[darling(default)]
+    transition: bool,
+    /// Generate a `{Name}Partial` type with every field optional
+    #[darling(default)]
+    partial: bool,
+    /// Extra derives to add to the generated partial type
+    #[darling(default)]
+    partial_derive: PathList,
+    /// Extra attributes to add to the generated partial type
+    #[darling(default)]
+    partial_attr: MetaList,
+    /// Extra attributes to add to every field of the generated partial type,
+    /// e.g. `partial_field_attr(serde(skip_serializing_if = "Option::is_none"))`
+    #[darling(default)]
+    partial_field_attr: MetaList,
+}
+
+/// A list of arbitrary attributes, e.g. `diff_attr(serde(rename_all = "camelCase"), repr(C))`
+#[derive(Debug, Default)]
+struct MetaList(Vec<syn::Meta>);
+
+impl FromMeta for MetaList {
+    fn from_list(items: &[NestedMeta]) -> darling::Result<Self> {
+        items
+            .iter()
+            .map(|item| match item {
+                NestedMeta::Meta(meta) => Ok(meta.clone()),
+                NestedMeta::Lit(lit) => Err(darling::Error::unexpected_lit_type(lit)),
+            })
+            .collect::<darling::Result<_>>()
+            .map(MetaList)
+    }
 }
 
 impl DeriveDiffable {
+    fn krate(&self) -> syn::Path {
+        self.krate
+            .clone()
+            .unwrap_or_else(|| syn::parse_quote! { difficient })
+    }
+
+    /// Whether to generate the `{Name}Field` enum, which every struct with fields
+    /// gets unless it is atomic or opts out with `#[diffable(fields = false)]`
+    fn has_field_enum(&self) -> bool {
+        let is_struct_with_fields = match &self.data {
+            Data::Struct(fields) => fields.style != Style::Unit,
+            Data::Enum(_) => false,
+        };
+        is_struct_with_fields && !self.atomic && self.fields != Some(false)
+    }
+
+    fn diff_ty(&self) -> Ident {
+        self.diff_name
+            .clone()
+            .unwrap_or_else(|| format_ident!("{}Diff", self.ident))
+    }
+
+    /// The derives and attributes to put on the generated diff type
+    fn diff_ty_attrs(&self) -> TokenStream {
+        let derives = self.diff_derive.iter();
+        let attrs = self.diff_attr.0.iter();
+        quote! {
+            #[derive(Debug, Clone, PartialEq #(, #derives)*)]
+            #( #[#attrs] )*
+        }
+    }
+
     fn derive(&self) -> TokenStream {
         if !self.generics.params.is_empty() {
             panic!("derive(Diffable) does not support generic parameters")
         }
+        let is_struct_with_fields = match &self.data {
+            Data::Struct(fields) => fields.style != Style::Unit,
+            Data::Enum(_) => false,
+        };
+        if (self.fields == Some(true) || self.mask || self.partial) && !is_struct_with_fields {
+            return syn::Error::new(
+                self.ident.span(),
+                "#[diffable(fields)], #[diffable(mask)] and #[diffable(partial)] are only supported on structs with fields",
+            )
+            .into_compile_error();
+        }
+        let all_fields: Vec<&StructLike> = match &self.data {
+            Data::Struct(fields) => fields.iter().collect(),
+            Data::Enum(variants) => variants.iter().flat_map(|var| var.fields.iter()).collect(),
+        };
+        if let Some(sl) = all_fields
+            .iter()
+            .find(|sl| sl.nested && !self.has_field_enum())
+        {
+            return syn::Error::new(
+                sl.ty.span(),
+                "#[diffable(nested)] only applies to the fields of structs with a field enum",
+            )
+            .into_compile_error();
+        }
+        if let Some(sl) = all_fields
+            .iter()
+            .find(|sl| sl.has_tolerance() && self.atomic)
+        {
+            return syn::Error::new(
+                sl.ty.span(),
+                "#[diffable(epsilon)], #[diffable(relative)] and #[diffable(ulps)] have no effect with #[diffable(atomic)]",
+            )
+            .into_compile_error();
+        }
+        if let Some(sl) = all_fields
+            .iter()
+            .find(|sl| sl.with.is_some() && sl.via.is_some())
+        {
+            return syn::Error::new(
+                sl.ty.span(),
+                "#[diffable(with)] and #[diffable(via)] cannot be used together",
+            )
+            .into_compile_error();
+        }
+        if let Some(sl) = all_fields
+            .iter()
+            .find(|sl| sl.has_tolerance() && (sl.with.is_some() || sl.via.is_some()))
+        {
+            return syn::Error::new(
+                sl.ty.span(),
+                "#[diffable(epsilon)], #[diffable(relative)] and #[diffable(ulps)] cannot be used with #[diffable(with)] or #[diffable(via)]",
+            )
+            .into_compile_error();
+        }
+        if self.atomic && (self.fields == Some(true) || self.partial || self.transition) {
+            return syn::Error::new(
+                self.ident.span(),
+                "#[diffable(fields)], #[diffable(partial)] and #[diffable(transition)] are not supported with #[diffable(atomic)]",
+            )
+            .into_compile_error();
+        }
+        if (self.mask || self.partial) && self.remote.is_some() {
+            return syn::Error::new(
+                self.ident.span(),
+                "#[diffable(mask)] and #[diffable(partial)] are not supported with #[diffable(remote)]",
+            )
+            .into_compile_error();
+        }
 
-        let name = &self.ident;
-        let diff_ty = format_ident!("{}Diff", self.ident);
+        let is_fieldless_enum = match &self.data {
+            Data::Struct(_) => false,
+            Data::Enum(variants) => variants.iter().all(|var| var.fields.is_empty()),
+        };
+        if self.atomic || is_fieldless_enum {
+            // these only affect the generated diff type, which atomic diffs don't have
+            let unused = [
+                self.diff_name
+                    .as_ref()
+                    .map(|name| (name.span(), "diff_name")),
+                self.diff_derive
+                    .first()
+                    .map(|path| (path.span(), "diff_derive")),
+                self.diff_attr
+                    .0
+                    .first()
+                    .map(|meta| (meta.span(), "diff_attr")),
+                self.eq_first
+                    .is_present()
+                    .then(|| (self.eq_first.span(), "eq_first")),
+            ];
+            if let Some((span, attr)) = unused.into_iter().flatten().next() {
+                return syn::Error::new(
+                    span,
+                    format!("#[diffable({attr})] has no effect with #[diffable(atomic)] or on fieldless enums, which are diffed with `AtomicDiff`"),
+                )
+                .into_compile_error();
+            }
+            return self.atomic_impl(is_fieldless_enum);
+        }
+
+        let diff_ty = self.diff_ty();
+        let krate = self.krate();
         let vis = &self.vis;
+        let diff_ty_attrs = self.diff_ty_attrs();
+        let parent = self.parent();
+        let self_ty = self.self_ty();
+        let this = self.this();
+        let eq_first = if self.eq_first.is_present() {
+            let equal = if all_fields.iter().any(|sl| sl.has_tolerance()) {
+                self.eq_within_tolerance()
+            } else {
+                quote! { #this == other }
+            };
+            quote! {
+                if std::ptr::eq(#this, other) || #equal {
+                    return #krate::DeepDiff::Unchanged;
+                }
+            }
+        } else {
+            quote! {}
+        };
 
         match &self.data {
             Data::Enum(variants) => {
                 let var_name: Vec<&Ident> = variants.iter().map(|ed| &ed.ident).collect();
-                let is_fieldless = variants.iter().all(|ed| ed.fields.is_empty());
-                let lifetime = if is_fieldless {
-                    quote! {}
-                } else {
-                    quote! { <'a> }
-                };
                 let var_diff_def = variants.iter().map(|var| match var.fields.style {
                     Style::Unit => quote! {},
                     Style::Tuple => {
-                        let ty = var.fields.iter().map(|data| &data.ty);
+                        let ty = var
+                            .fields
+                            .iter()
+                            .map(|data| data.diff_ty(&krate, quote! { 'a }));
                         quote! {
                             (
-                                #(  <#ty as difficient::Diffable<'a>>::Diff, )*
+                                #(  #ty, )*
                             )
                         }
                     }
@@ -63,75 +343,97 @@ impl DeriveDiffable {
                             .iter()
                             .map(|data| &data.ident)
                             .collect::<Vec<_>>();
-                        let ty = var.fields.iter().map(|data| &data.ty);
+                        let ty = var
+                            .fields
+                            .iter()
+                            .map(|data| data.diff_ty(&krate, quote! { 'a }));
                         quote! {
                             {
-                                #( #field: <#ty as difficient::Diffable<'a>>::Diff, )*
+                                #( #field: #ty, )*
                             }
                         }
                     }
                 });
 
+                let transition = if self.transition {
+                    match self.transition(variants) {
+                        Ok(transition) => transition,
+                        Err(e) => return e.into_compile_error(),
+                    }
+                } else {
+                    None
+                };
+                let Transition {
+                    defs: transition_defs,
+                    variant_def: transition_variant_def,
+                    diff_arms: transition_diff_arms,
+                    apply_arms: transition_apply_arms,
+                } = transition.unwrap_or_default();
+
                 let enum_definition = quote! {
-                    #[derive(Debug, Clone, PartialEq)]
+                    #diff_ty_attrs
                     #[allow(non_camel_case_types)]
                     #[allow(non_snake_case)]
                     #[allow(dead_code)]
                     #[automatically_derived]
-                    #vis enum #diff_ty #lifetime {
+                    #vis enum #diff_ty<'a> {
                         #(
                             #var_name #var_diff_def,
                         )*
+                        #transition_variant_def
                     }
+
+                    #transition_defs
                 };
 
                 let variant_diff_impl = variants.iter().zip(var_name.iter()).map(|(var, var_name)| {
-                    let pattern_match_left = pattern_match(&var.fields, "left");
-                    let pattern_match_right = pattern_match(&var.fields, "right");
-                    let diff_impl = diff_body(&diff_ty, var_name, &var.fields);
+                    let rest = self.remote_rest(&var.attrs);
+                    let pattern_match_left = pattern_match(&var.fields, "left", rest);
+                    let pattern_match_right = pattern_match(&var.fields, "right", rest);
+                    let diff_impl = diff_body(&krate, &diff_ty, var_name, &var.fields);
                     quote! {
-                        (Self::#var_name #pattern_match_left, Self::#var_name #pattern_match_right)  => {
+                        (#self_ty::#var_name #pattern_match_left, #self_ty::#var_name #pattern_match_right)  => {
                             #diff_impl
                         }
                     }
                 });
 
-                let diffable_impl = quote! {
-                    impl<'a> difficient::Diffable<'a> for #name {
-                        type Diff = difficient::DeepDiff<'a, Self, #diff_ty #lifetime>;
-
-                        #[allow(non_snake_case)]
-                        fn diff(&self, other: &'a Self) -> Self::Diff {
-                            use difficient::Replace as _;
-                            match (self, other) {
-                                #(
-                                    #variant_diff_impl
-                                ),*
-                                _ => difficient::DeepDiff::Replaced(other)
-                            }
+                let diffable_impl = self.diffable_impl(
+                    quote! { #krate::DeepDiff<'a, #self_ty, #diff_ty<'a>> },
+                    quote! {
+                        use #krate::Replace as _;
+                        #eq_first
+                        match (#this, other) {
+                            #(
+                                #variant_diff_impl
+                            ),*
+                            #transition_diff_arms
+                            _ => #krate::DeepDiff::Replaced(other)
                         }
-                    }
-                };
+                    },
+                );
 
                 let apply_body =variants.iter().zip(var_name.iter()).map(|(var, var_name)| {
                     let pat_l = prefixed_idents(&var.fields, "left");
                     let pat_r = prefixed_idents(&var.fields, "right");
-                    let pattern_match_left = pattern_match(&var.fields, "left");
-                    let pattern_match_right = pattern_match(&var.fields, "right");
+                    let pattern_match_left = pattern_match(&var.fields, "left", false);
+                    let pattern_match_right =
+                        pattern_match(&var.fields, "right", self.remote_rest(&var.attrs));
                     quote! {
-                        (Self::#var_name #pattern_match_left, #name::#var_name #pattern_match_right)  => {
+                        (Self::#var_name #pattern_match_left, #parent::#var_name #pattern_match_right)  => {
                             #( #pat_l.apply_to_base(#pat_r, errs); )*
                         }
                     }
                 }).collect::<Vec<_>>();
 
                 let apply_impl = quote! {
-                    impl #lifetime difficient::Apply for #diff_ty #lifetime {
-                        type Parent = #name;
-                        fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<difficient::ApplyError>) {
+                    impl<'a> #krate::Apply for #diff_ty<'a> {
+                        type Parent = #parent;
+                        fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<#krate::ApplyError>) {
                             match (self, source) {
                                 #( #apply_body )*
-                                _ => errs.push(difficient::ApplyError::MismatchingEnum),
+                                #transition_apply_arms
+                                _ => errs.push(#krate::ApplyError::MismatchingEnum),
                             }
                         }
                     }
@@ -146,18 +448,12 @@ impl DeriveDiffable {
                 }
             }
             Data::Struct(fields) => {
-                let ty = fields.iter().map(|data| &data.ty).collect::<Vec<_>>();
                 if let Style::Unit = fields.style {
                     // short-circuit return
-                    return quote! {
-                        impl<'a> difficient::Diffable<'a> for #name {
-                            type Diff = difficient::Id<Self>;
-
-                            fn diff(&self, other: &'a Self) -> Self::Diff {
-                                difficient::Id::new()
-                            }
-                        }
-                    };
+                    return self.diffable_impl(
+                        quote! { #krate::Id<#self_ty> },
+                        quote! { #krate::Id::new() },
+                    );
                 };
 
                 let allows = quote! {
@@ -168,13 +464,20 @@ impl DeriveDiffable {
                 };
                 let field = idents(fields);
                 let accessor = accessors(fields);
+                let ty = fields
+                    .iter()
+                    .map(|data| data.diff_ty(&krate, quote! { 'a }))
+                    .collect::<Vec<_>>();
+                let diff_call = fields.iter().zip(&accessor).map(|(data, acc)| {
+                    data.diff_call(&krate, quote! { &#this.#acc }, quote! { &other.#acc })
+                });
                 let diff_ty_def = match fields.style {
                     Style::Tuple => {
                         quote! {
                             #allows
                             #vis struct #diff_ty<'a>(
                                 #(
-                                    <#ty as difficient::Diffable<'a>>::Diff,
+                                    #ty,
                                 )*
                             );
                         }
@@ -184,13 +487,23 @@ impl DeriveDiffable {
                             #allows
                             #vis struct #diff_ty<'a> {
                                 #(
-                                    #field: <#ty as difficient::Diffable<'a>>::Diff,
+                                    #field: #ty,
                                 )*
                             }
                         }
                     }
                     Style::Unit => unreachable!(),
                 };
+                let field_enum = if self.has_field_enum() {
+                    self.field_enum(fields)
+                } else {
+                    quote! {}
+                };
+                let mask_impl = if self.mask {
+                    self.mask_impl(fields)
+                } else {
+                    quote! {}
+                };
                 let patched_impl = match fields.style {
                     Style::Tuple => quote! {
                         #diff_ty( #( #field, )* )
@@ -200,36 +513,739 @@ impl DeriveDiffable {
                     },
                     Style::Unit => unreachable!(),
                 };
+                let partial_impl = if self.partial {
+                    self.partial_impl(fields, &patched_impl)
+                } else {
+                    quote! {}
+                };
+                let diffable_impl = self.diffable_impl(
+                    quote! { #krate::DeepDiff<'a, #self_ty, #diff_ty<'a>> },
+                    quote! {
+                        use #krate::Replace as _;
+                        #eq_first
+                        #(
+                            let #field = #diff_call;
+                        )*
+                        if #( #field.is_unchanged() && )* true {
+                            #krate::DeepDiff::Unchanged
+                        } else if #( #field.is_replaced() && )* true {
+                            #krate::DeepDiff::Replaced(other)
+                        } else {
+                            #krate::DeepDiff::Patched(#patched_impl)
+                        }
+                    },
+                );
                 quote! {
-                    #[derive(Debug, Clone, PartialEq)]
+                    #diff_ty_attrs
                     #diff_ty_def
 
-                    impl<'a> difficient::Diffable<'a> for #name {
-                        type Diff = difficient::DeepDiff<'a, Self, #diff_ty<'a>>;
+                    #diffable_impl
 
+                    impl<'a> #krate::Apply for #diff_ty<'a> {
+                        type Parent = #parent;
                         #[allow(non_snake_case)]
-                        fn diff(&self, other: &'a Self) -> Self::Diff {
-                            use difficient::Replace as _;
-                            #(
-                                let #field = self.#accessor.diff(&other.#accessor);
-                            )*
-                            if #( #field.is_unchanged() && )* true {
-                                difficient::DeepDiff::Unchanged
-                            } else if #( #field.is_replaced() && )* true {
-                                difficient::DeepDiff::Replaced(other)
-                            } else {
-                                difficient::DeepDiff::Patched(#patched_impl)
-                            }
+                        fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<#krate::ApplyError>) {
+                            #( self.#accessor.apply_to_base(&mut source.#accessor, errs) );*
                         }
                     }
 
-                    impl<'a> difficient::Apply for #diff_ty<'a> {
-                        type Parent = #name;
-                        #[allow(non_snake_case)]
-                        fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<difficient::ApplyError>) {
-                            #( self.#accessor.apply_to_base(&mut source.#accessor, errs) );*
+                    #field_enum
+
+                    #mask_impl
+
+                    #partial_impl
+                }
+            }
+        }
+    }
+
+    /// Implement `Diffable` with an `AtomicDiff`. Fieldless enums are compared by discriminant,
+    /// anything else with `PartialEq`.
+    fn atomic_impl(&self, is_fieldless_enum: bool) -> TokenStream {
+        let krate = self.krate();
+        let self_ty = self.self_ty();
+        let this = self.this();
+        let is_unchanged = if is_fieldless_enum {
+            quote! { std::mem::discriminant(#this) == std::mem::discriminant(other) }
+        } else {
+            quote! { #this == other }
+        };
+        let diffable_impl = self.diffable_impl(
+            quote! { #krate::AtomicDiff<'a, #self_ty> },
+            quote! {
+                if #is_unchanged {
+                    #krate::AtomicDiff::Unchanged
+                } else {
+                    #krate::AtomicDiff::Replaced(other)
+                }
+            },
+        );
+        let mask_impl = match &self.data {
+            Data::Struct(fields) if self.mask => self.mask_impl(fields),
+            _ => quote! {},
+        };
+        quote! {
+            #diffable_impl
+
+            #mask_impl
+        }
+    }
+
+    /// Compare field-by-field for `eq_first`, so that fields within their tolerance are equal
+    fn eq_within_tolerance(&self) -> TokenStream {
+        let krate = self.krate();
+        let self_ty = self.self_ty();
+        let this = self.this();
+        match &self.data {
+            Data::Struct(fields) => {
+                let eq_call = fields.iter().zip(accessors(fields)).map(|(sl, acc)| {
+                    sl.eq_call(&krate, quote! { &#this.#acc }, quote! { &other.#acc })
+                });
+                quote! { ( #( #eq_call && )* true ) }
+            }
+            Data::Enum(variants) => {
+                let arm = variants.iter().map(|var| {
+                    let var_name = &var.ident;
+                    let rest = self.remote_rest(&var.attrs);
+                    let pattern_match_left = pattern_match(&var.fields, "left", rest);
+                    let pattern_match_right = pattern_match(&var.fields, "right", rest);
+                    let left = prefixed_idents(&var.fields, "left");
+                    let right = prefixed_idents(&var.fields, "right");
+                    let eq_call = var
+                        .fields
+                        .iter()
+                        .zip(left.iter().zip(&right))
+                        .map(|(sl, (left, right))| {
+                            sl.eq_call(&krate, quote! { #left }, quote! { #right })
+                        });
+                    quote! {
+                        (#self_ty::#var_name #pattern_match_left, #self_ty::#var_name #pattern_match_right) => {
+                            #( #eq_call && )* true
                         }
                     }
+                });
+                quote! {
+                    match (#this, other) {
+                        #( #arm )*
+                        _ => false,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether patterns on the foreign type of a `remote` mirror need a trailing `..`, because
+    /// the mirror marks the type (or variant) with these attributes as `#[non_exhaustive]`
+    fn remote_rest(&self, attrs: &[syn::Attribute]) -> bool {
+        self.remote.is_some()
+            && attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("non_exhaustive"))
+    }
+
+    /// The type being diffed, as a path: the deriving type, or the foreign type for `remote`
+    fn parent(&self) -> TokenStream {
+        match &self.remote {
+            Some(remote) => quote! { #remote },
+            None => {
+                let name = &self.ident;
+                quote! { #name }
+            }
+        }
+    }
+
+    /// The type being diffed, from within the `Diffable` (or `DiffableRemote`) impl
+    fn self_ty(&self) -> TokenStream {
+        match &self.remote {
+            Some(remote) => quote! { #remote },
+            None => quote! { Self },
+        }
+    }
+
+    /// The left-hand value being diffed, from within the `diff` method
+    fn this(&self) -> TokenStream {
+        match &self.remote {
+            Some(_) => quote! { this },
+            None => quote! { self },
+        }
+    }
+
+    /// Check that a `remote` mirror has the same fields as the foreign type, by destructuring
+    /// the foreign type exhaustively and converting its fields into the mirror. The mirror is
+    /// never otherwise used, so also read its fields.
+    ///
+    /// Only a mirror marked `#[non_exhaustive]` may leave out fields (or variants).
+    fn remote_check(&self) -> TokenStream {
+        let Some(remote) = &self.remote else {
+            return quote! {};
+        };
+        let name = &self.ident;
+        let (convert, read) = match &self.data {
+            Data::Struct(fields) => {
+                let accessor = accessors(fields);
+                let pat = pattern_match(fields, "field", self.remote_rest(&self.attrs));
+                let construct = construct(fields, "field");
+                let convert = quote! {
+                    {
+                        let #remote #pat = remote;
+                        #name #construct
+                    }
+                };
+                let read = quote! { let _ = ( #( &mirror.#accessor, )* ); };
+                (convert, read)
+            }
+            Data::Enum(variants) => {
+                let convert_arm = variants.iter().map(|var| {
+                    let var_name = &var.ident;
+                    let pat = pattern_match(&var.fields, "field", self.remote_rest(&var.attrs));
+                    let construct = construct(&var.fields, "field");
+                    quote! { #remote::#var_name #pat => #name::#var_name #construct }
+                });
+                let read_arm = variants.iter().map(|var| {
+                    let var_name = &var.ident;
+                    let pat = pattern_match(&var.fields, "field", false);
+                    let binding = prefixed_idents(&var.fields, "field");
+                    quote! { #name::#var_name #pat => { let _ = ( #( #binding, )* ); } }
+                });
+                let rest_arm = if self.remote_rest(&self.attrs) {
+                    quote! { _ => unreachable!(), }
+                } else {
+                    quote! {}
+                };
+                let convert = quote! {
+                    match remote {
+                        #( #convert_arm, )*
+                        #rest_arm
+                    }
+                };
+                let read = quote! {
+                    match mirror {
+                        #( #read_arm )*
+                    }
+                };
+                (convert, read)
+            }
+        };
+        quote! {
+            #[allow(unreachable_patterns)]
+            const _: fn(#remote) -> #name = |remote| #convert;
+            const _: fn(&#name) = |mirror| { #read };
+        }
+    }
+
+    /// Implement `Diffable` for the deriving type, or `DiffableRemote` for a `remote` mirror
+    fn diffable_impl(&self, diff: TokenStream, body: TokenStream) -> TokenStream {
+        let krate = self.krate();
+        let name = &self.ident;
+        let remote_check = self.remote_check();
+        match &self.remote {
+            Some(remote) => quote! {
+                #remote_check
+
+                impl<'a> #krate::DiffableRemote<'a> for #name {
+                    type Remote = #remote;
+                    type Diff = #diff;
+
+                    #[allow(non_snake_case)]
+                    fn diff(this: &#remote, other: &'a #remote) -> Self::Diff {
+                        use #krate::Diffable as _;
+                        #body
+                    }
+                }
+            },
+            None => quote! {
+                impl<'a> #krate::Diffable<'a> for #name {
+                    type Diff = #diff;
+
+                    #[allow(non_snake_case)]
+                    fn diff(&self, other: &'a Self) -> Self::Diff {
+                        #body
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// The extra code generated by `#[diffable(transition)]`
+#[derive(Default)]
+struct Transition {
+    /// Definitions of the `{DiffName}Variant`, `{DiffName}Shared` and `{DiffName}New` types
+    defs: TokenStream,
+    /// The `VariantChanged` variant of the diff type
+    variant_def: TokenStream,
+    /// Match arms to diff between different variants
+    diff_arms: TokenStream,
+    /// Match arms to apply a `VariantChanged` diff
+    apply_arms: TokenStream,
+}
+
+impl DeriveDiffable {
+    /// Generate diffs between different enum variants.
+    ///
+    /// Named fields which appear in more than one variant are 'shared', and are diffed
+    /// when switching between those variants. Any other fields of the target variant are 'new'.
+    /// Returns `None` if no fields are shared.
+    fn transition(&self, variants: &[EnumData]) -> syn::Result<Option<Transition>> {
+        let krate = self.krate();
+        let vis = &self.vis;
+        let diff_ty = self.diff_ty();
+        let diff_ty_attrs = self.diff_ty_attrs();
+        let parent = self.parent();
+        let self_ty = self.self_ty();
+        let this = self.this();
+        let variant_ty = format_ident!("{}Variant", diff_ty);
+        let shared_ty = format_ident!("{}Shared", diff_ty);
+        let new_ty = format_ident!("{}New", diff_ty);
+
+        // the key of each field: named fields by name, positional fields by variant and position
+        let key = |var: &EnumData, ix: usize, sl: &StructLike| match &sl.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("{}_{ix}", var.ident),
+        };
+        // the variants in which each named field appears
+        let mut shared: Vec<(Ident, &StructLike, Vec<&Ident>)> = Vec::new();
+        let mut new: Vec<(Ident, &StructLike)> = Vec::new();
+        for var in variants {
+            for (ix, sl) in var.fields.iter().enumerate() {
+                let key = key(var, ix, sl);
+                match shared.iter_mut().find(|(k, _, _)| *k == key) {
+                    Some((_, first, in_variants)) => {
+                        let diff_ty =
+                            |sl: &StructLike| sl.diff_ty(&krate, quote! { 'a }).to_string();
+                        if diff_ty(first) != diff_ty(sl) {
+                            return Err(syn::Error::new(
+                                sl.ty.span(),
+                                format!(
+                                    "#[diffable(transition)] requires field `{key}` to have the same type in every variant"
+                                ),
+                            ));
+                        }
+                        in_variants.push(&var.ident);
+                    }
+                    None => {
+                        shared.push((key.clone(), sl, vec![&var.ident]));
+                        new.push((key, sl));
+                    }
+                }
+            }
+        }
+        shared.retain(|(_, _, in_variants)| in_variants.len() > 1);
+        if shared.is_empty() {
+            return Ok(None);
+        }
+
+        let var_name = variants.iter().map(|var| &var.ident).collect::<Vec<_>>();
+        let shared_key = shared.iter().map(|(k, _, _)| k).collect::<Vec<_>>();
+        let shared_field_ty = shared
+            .iter()
+            .map(|(_, sl, _)| sl.diff_ty(&krate, quote! { 'a }));
+        let new_key = new.iter().map(|(k, _)| k);
+        let new_field_ty = new.iter().map(|(_, sl)| &sl.ty);
+        let defs = quote! {
+            #diff_ty_attrs
+            #[derive(Copy)]
+            #[allow(non_camel_case_types)]
+            #[allow(dead_code)]
+            #[automatically_derived]
+            #vis enum #variant_ty {
+                #( #var_name, )*
+            }
+
+            #diff_ty_attrs
+            #[derive(Default)]
+            #[allow(non_camel_case_types)]
+            #[allow(non_snake_case)]
+            #[automatically_derived]
+            #vis struct #shared_ty<'a> {
+                #( #shared_key: Option<#shared_field_ty>, )*
+            }
+
+            #diff_ty_attrs
+            #[derive(Default)]
+            #[allow(non_camel_case_types)]
+            #[allow(non_snake_case)]
+            #[automatically_derived]
+            #vis struct #new_ty<'a> {
+                #( #new_key: Option<&'a #new_field_ty>, )*
+            }
+        };
+
+        let variant_def = quote! {
+            VariantChanged {
+                to: #variant_ty,
+                shared: #shared_ty<'a>,
+                new: #new_ty<'a>,
+            }
+        };
+
+        let mut diff_arms = TokenStream::new();
+        let mut apply_arms = TokenStream::new();
+        for var in variants {
+            let var_name = &var.ident;
+            let keys = var
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(ix, sl)| key(var, ix, sl))
+                .collect::<Vec<_>>();
+            if !keys.iter().any(|k| shared_key.contains(&k)) {
+                // nothing to reuse, fall back to replacing
+                continue;
+            }
+            let right = prefixed_idents(&var.fields, "right");
+            let pattern_match_right =
+                pattern_match(&var.fields, "right", self.remote_rest(&var.attrs));
+            let construct_right = construct(&var.fields, "right");
+
+            let mut collect = Vec::new();
+            let mut check = Vec::new();
+            let mut field_value = Vec::new();
+            for ((k, sl), right) in keys.iter().zip(var.fields.iter()).zip(&right) {
+                match shared.iter().find(|(key, _, _)| key == k) {
+                    Some((_, _, in_variants)) => {
+                        let diff_call = sl.diff_call(&krate, quote! { left }, quote! { #right });
+                        collect.push(quote! {
+                            #[allow(unreachable_patterns)]
+                            match #this {
+                                #( #self_ty::#in_variants { #k: left, .. } )|* => {
+                                    shared.#k = Some(#diff_call);
+                                }
+                                _ => new.#k = Some(#right),
+                            }
+                        });
+                        check.push(quote! {
+                            #[allow(unreachable_patterns)]
+                            let fits = match (&shared.#k, &new.#k, &*source) {
+                                (Some(_), _, #( #parent::#in_variants { .. } )|*) => true,
+                                (None, Some(_), _) => true,
+                                _ => false,
+                            };
+                            if !fits {
+                                errs.push(#krate::ApplyError::MismatchingEnum);
+                                return;
+                            }
+                        });
+                        field_value.push(quote! {
+                            #[allow(unreachable_patterns)]
+                            let #right = match (&shared.#k, new.#k, &*source) {
+                                (Some(diff), _, #( #parent::#in_variants { #k: value, .. } )|*) => {
+                                    let mut value = value.clone();
+                                    diff.apply_to_base(&mut value, errs);
+                                    value
+                                }
+                                (None, Some(value), _) => value.clone(),
+                                _ => unreachable!(),
+                            };
+                        });
+                    }
+                    None => {
+                        collect.push(quote! { new.#k = Some(#right); });
+                        check.push(quote! {
+                            if new.#k.is_none() {
+                                errs.push(#krate::ApplyError::MismatchingEnum);
+                                return;
+                            }
+                        });
+                        field_value.push(quote! {
+                            let #right = match new.#k {
+                                Some(value) => value.clone(),
+                                None => unreachable!(),
+                            };
+                        });
+                    }
+                }
+            }
+            let var_shared_key = keys.iter().filter(|k| shared_key.contains(k));
+
+            diff_arms.extend(quote! {
+                (_, #self_ty::#var_name #pattern_match_right) => {
+                    let mut shared = #shared_ty::default();
+                    let mut new = #new_ty::default();
+                    #( #collect )*
+                    if #( shared.#var_shared_key.as_ref().is_some_and(|diff| !diff.is_replaced()) || )* false {
+                        #krate::DeepDiff::Patched(#diff_ty::VariantChanged {
+                            to: #variant_ty::#var_name,
+                            shared,
+                            new,
+                        })
+                    } else {
+                        #krate::DeepDiff::Replaced(other)
+                    }
+                }
+            });
+            apply_arms.extend(quote! {
+                (Self::VariantChanged { to: #variant_ty::#var_name, shared, new }, source) => {
+                    // check that the diff fits before changing the source
+                    #( #check )*
+                    #( #field_value )*
+                    *source = #parent::#var_name #construct_right;
+                }
+            });
+        }
+
+        Ok(Some(Transition {
+            defs,
+            variant_def,
+            diff_arms,
+            apply_arms,
+        }))
+    }
+}
+
+impl DeriveDiffable {
+    /// Generate the `{Name}Field` enum, with one variant per struct field,
+    /// and implement `ChangedFields` for the diff type
+    fn field_enum(&self, fields: &Fields<StructLike>) -> TokenStream {
+        let krate = self.krate();
+        let vis = &self.vis;
+        let diff_ty = self.diff_ty();
+        let field_ty = format_ident!("{}Field", self.ident);
+        let accessor = accessors(fields);
+        let variant = field_variants(fields);
+        for (ix, var) in variant.iter().enumerate() {
+            if let Some(first) = variant[..ix].iter().position(|other| other == var) {
+                let (first, sl) = (&idents(fields)[first], &fields.fields[ix]);
+                let span = sl
+                    .ident
+                    .as_ref()
+                    .map_or_else(|| sl.ty.span(), |ident| ident.span());
+                return syn::Error::new(
+                    span,
+                    format!("the field enum names this field `{field_ty}::{var}`, which is already the name of field `{first}`; rename one of them or opt out with #[diffable(fields = false)]"),
+                )
+                .into_compile_error();
+            }
+        }
+
+        let variant_def = fields.iter().zip(&variant).map(|(sl, var)| {
+            if sl.nested {
+                let ty = sl.diff_ty(&krate, quote! { 'static });
+                quote! {
+                    #var(<#ty as #krate::ChangedFields>::Field)
+                }
+            } else {
+                quote! { #var }
+            }
+        });
+        let changed = fields.iter().zip(&variant).zip(&accessor).map(|((sl, var), acc)| {
+            if sl.nested {
+                quote! {
+                    .chain(#krate::ChangedFields::changed_fields(&self.#acc).map(#field_ty::#var))
+                }
+            } else {
+                quote! {
+                    .chain((!self.#acc.is_unchanged()).then_some(#field_ty::#var))
+                }
+            }
+        });
+
+        quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[allow(non_camel_case_types)]
+            #[automatically_derived]
+            #vis enum #field_ty {
+                #( #variant_def, )*
+            }
+
+            impl<'a> #krate::ChangedFields for #diff_ty<'a> {
+                type Field = #field_ty;
+
+                fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+                    use #krate::Replace as _;
+                    std::iter::empty()
+                        #( #changed )*
+                }
+            }
+        }
+    }
+}
+
+impl DeriveDiffable {
+    /// Generate the `{Name}Mask` bitflags-style type, with one bit per struct field,
+    /// and a `changed_mask` method which compares fields with `PartialEq`, or within
+    /// their tolerance if they have one, without building any diffs
+    fn mask_impl(&self, fields: &Fields<StructLike>) -> TokenStream {
+        let name = &self.ident;
+        let vis = &self.vis;
+        let mask_ty = format_ident!("{}Mask", self.ident);
+        if fields.len() > 64 {
+            return syn::Error::new(
+                self.ident.span(),
+                "#[diffable(mask)] supports at most 64 fields",
+            )
+            .into_compile_error();
+        }
+        let krate = self.krate();
+        let accessor = accessors(fields);
+        let changed = fields.iter().zip(&accessor).map(|(sl, acc)| {
+            let eq_call = sl.eq_call(&krate, quote! { &self.#acc }, quote! { &other.#acc });
+            quote! { !(#eq_call) }
+        });
+        let flag = idents(fields)
+            .iter()
+            .map(|ident| format_ident!("{}", ident.unraw().to_string().to_uppercase()))
+            .collect::<Vec<_>>();
+        let bit = 0..fields.len() as u64;
+        let all = if fields.len() == 64 {
+            u64::MAX
+        } else {
+            (1u64 << fields.len()) - 1
+        };
+
+        quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+            #[automatically_derived]
+            #vis struct #mask_ty(u64);
+
+            #[allow(dead_code)]
+            #[allow(non_upper_case_globals)]
+            impl #mask_ty {
+                #( #vis const #flag: Self = Self(1 << #bit); )*
+
+                #vis const fn empty() -> Self {
+                    Self(0)
+                }
+
+                #vis const fn all() -> Self {
+                    Self(#all)
+                }
+
+                #vis const fn bits(&self) -> u64 {
+                    self.0
+                }
+
+                #vis const fn is_empty(&self) -> bool {
+                    self.0 == 0
+                }
+
+                #vis const fn contains(&self, other: Self) -> bool {
+                    self.0 & other.0 == other.0
+                }
+
+                #vis const fn intersects(&self, other: Self) -> bool {
+                    self.0 & other.0 != 0
+                }
+            }
+
+            impl std::ops::BitOr for #mask_ty {
+                type Output = Self;
+                fn bitor(self, other: Self) -> Self {
+                    Self(self.0 | other.0)
+                }
+            }
+
+            impl std::ops::BitOrAssign for #mask_ty {
+                fn bitor_assign(&mut self, other: Self) {
+                    self.0 |= other.0
+                }
+            }
+
+            impl std::ops::BitAnd for #mask_ty {
+                type Output = Self;
+                fn bitand(self, other: Self) -> Self {
+                    Self(self.0 & other.0)
+                }
+            }
+
+            impl std::ops::BitAndAssign for #mask_ty {
+                fn bitand_assign(&mut self, other: Self) {
+                    self.0 &= other.0
+                }
+            }
+
+            impl #name {
+                #[allow(dead_code)]
+                #vis fn changed_mask(&self, other: &Self) -> #mask_ty {
+                    let mut mask = #mask_ty::empty();
+                    #(
+                        let changed = #changed;
+                        if changed {
+                            mask |= #mask_ty::#flag;
+                        }
+                    )*
+                    mask
+                }
+            }
+        }
+    }
+}
+
+impl DeriveDiffable {
+    /// Generate the `{Name}Partial` type, where every field is an `Option`,
+    /// and its conversion into the diff type
+    fn partial_impl(&self, fields: &Fields<StructLike>, patched_impl: &TokenStream) -> TokenStream {
+        let krate = self.krate();
+        let name = &self.ident;
+        let vis = &self.vis;
+        let diff_ty = self.diff_ty();
+        let partial_ty = format_ident!("{}Partial", self.ident);
+        let field = idents(fields);
+        let accessor = accessors(fields);
+        let derives = self.partial_derive.iter();
+        let attrs = self.partial_attr.0.iter();
+        let field_attrs = self.partial_field_attr.0.iter();
+        let field_attr = quote! { #( #[#field_attrs] )* };
+        let field_def = fields.iter().map(|sl| {
+            let StructLike { ident, ty, vis, .. } = sl;
+            match ident {
+                Some(ident) => quote! { #field_attr #vis #ident: Option<#ty> },
+                None => quote! { #field_attr #vis Option<#ty> },
+            }
+        });
+        let partial_ty_def = match fields.style {
+            Style::Tuple => quote! {
+                #vis struct #partial_ty( #( #field_def, )* );
+            },
+            Style::Struct => quote! {
+                #vis struct #partial_ty { #( #field_def, )* }
+            },
+            Style::Unit => unreachable!(),
+        };
+
+        quote! {
+            #[derive(Debug, Clone, PartialEq, Default #(, #derives)*)]
+            #( #[#attrs] )*
+            #[allow(non_camel_case_types)]
+            #[allow(non_snake_case)]
+            #[automatically_derived]
+            #partial_ty_def
+
+            impl<'a> From<&'a #partial_ty> for #diff_ty<'a> {
+                #[allow(non_snake_case)]
+                fn from(partial: &'a #partial_ty) -> Self {
+                    #(
+                        let #field = match &partial.#accessor {
+                            Some(value) => #krate::Replacement::replaced_by(value),
+                            None => #krate::Replacement::unchanged(),
+                        };
+                    )*
+                    #patched_impl
+                }
+            }
+
+            // an owned partial sets the fields which are present
+            impl #krate::Apply for #partial_ty {
+                type Parent = #name;
+                #[allow(non_snake_case)]
+                fn apply_to_base(&self, source: &mut Self::Parent, _: &mut Vec<#krate::ApplyError>) {
+                    #(
+                        if let Some(value) = &self.#accessor {
+                            source.#accessor = value.clone();
+                        }
+                    )*
+                }
+            }
+
+            impl #partial_ty {
+                /// The diff which sets every field that is present
+                #[allow(dead_code)]
+                #vis fn to_diff(&self) -> <#name as #krate::Diffable<'_>>::Diff {
+                    if #( self.#accessor.is_none() && )* true {
+                        #krate::DeepDiff::Unchanged
+                    } else {
+                        #krate::DeepDiff::Patched(self.into())
+                    }
                 }
             }
         }
@@ -242,7 +1258,12 @@ impl ToTokens for DeriveDiffable {
     }
 }
 
-fn diff_body(diff_ty: &Ident, variant_name: &Ident, fields: &Fields<StructLike>) -> TokenStream {
+fn diff_body(
+    krate: &syn::Path,
+    diff_ty: &Ident,
+    variant_name: &Ident,
+    fields: &Fields<StructLike>,
+) -> TokenStream {
     let ident = idents(fields);
 
     let patch_ctor = match fields.style {
@@ -264,35 +1285,46 @@ fn diff_body(diff_ty: &Ident, variant_name: &Ident, fields: &Fields<StructLike>)
     match fields.style {
         Style::Unit => quote! {
             // if unit-types match, by definition they are unchanged
-            difficient::DeepDiff::Unchanged
+            #krate::DeepDiff::Unchanged
         },
         Style::Tuple | Style::Struct => {
             let left_ident = prefixed_idents(fields, "left");
             let right_ident = prefixed_idents(fields, "right");
+            let diff_call = fields.iter().zip(left_ident.iter().zip(&right_ident)).map(
+                |(data, (left, right))| data.diff_call(krate, quote! { #left }, quote! { #right }),
+            );
             quote! {
                 #(
-                    let #ident = #left_ident.diff(#right_ident);
+                    let #ident = #diff_call;
                 )*
                 if #( #ident.is_unchanged() && )* true {
-                    difficient::DeepDiff::Unchanged
+                    #krate::DeepDiff::Unchanged
                 } else if #( #ident.is_replaced() && )* true {
-                    difficient::DeepDiff::Replaced(other)
+                    #krate::DeepDiff::Replaced(other)
                 } else {
-                    difficient::DeepDiff::Patched(#patch_ctor)
+                    #krate::DeepDiff::Patched(#patch_ctor)
                 }
             }
         }
     }
 }
 
-fn pattern_match(fields: &Fields<StructLike>, prefix: &str) -> TokenStream {
-    let pat = prefixed_idents(&fields, prefix);
+/// A pattern binding each field to a prefixed ident. With `rest`, the pattern ends in `..`,
+/// to match `#[non_exhaustive]` variants of foreign types.
+fn pattern_match(fields: &Fields<StructLike>, prefix: &str, rest: bool) -> TokenStream {
+    let pat = prefixed_idents(fields, prefix);
+    let rest = if rest {
+        quote! { .. }
+    } else {
+        quote! {}
+    };
     match fields.style {
-        Style::Unit => quote! {},
+        Style::Unit if rest.is_empty() => quote! {},
+        Style::Unit => quote! { { .. } },
         Style::Tuple => {
             quote! {
                 (
-                    #(  #pat, )*
+                    #(  #pat, )* #rest
                 )
             }
         }
@@ -300,13 +1332,26 @@ fn pattern_match(fields: &Fields<StructLike>, prefix: &str) -> TokenStream {
             let id = fields.iter().map(|data| &data.ident).collect::<Vec<_>>();
             quote! {
                 {
-                    #( #id: #pat, )*
+                    #( #id: #pat, )* #rest
                 }
             }
         }
     }
 }
 
+/// Construct a value from prefixed idents, the inverse of `pattern_match`
+fn construct(fields: &Fields<StructLike>, prefix: &str) -> TokenStream {
+    let pat = prefixed_idents(fields, prefix);
+    match fields.style {
+        Style::Unit => quote! {},
+        Style::Tuple => quote! { ( #( #pat, )* ) },
+        Style::Struct => {
+            let id = fields.iter().map(|data| &data.ident).collect::<Vec<_>>();
+            quote! { { #( #id: #pat, )* } }
+        }
+    }
+}
+
 fn prefixed_idents(fields: &Fields<StructLike>, prefix: &str) -> Vec<Ident> {
     fields
         .iter()
@@ -335,6 +1380,32 @@ fn idents(fields: &Fields<StructLike>) -> Vec<Ident> {
         .collect()
 }
 
+/// UpperCamelCase variant names for each field, e.g. `first_name` -> `FirstName`, `0` -> `F0`
+fn field_variants(fields: &Fields<StructLike>) -> Vec<Ident> {
+    idents(fields)
+        .iter()
+        .enumerate()
+        .map(|(ix, ident)| {
+            let camel: String = ident
+                .unraw()
+                .to_string()
+                .split('_')
+                .filter(|part| !part.is_empty())
+                .map(|part| {
+                    let mut chars = part.chars();
+                    let first = chars.next().unwrap().to_ascii_uppercase();
+                    std::iter::once(first).chain(chars).collect::<String>()
+                })
+                .collect();
+            if camel.is_empty() {
+                format_ident!("F{ix}")
+            } else {
+                format_ident!("{camel}")
+            }
+        })
+        .collect()
+}
+
 fn accessors(fields: &Fields<StructLike>) -> Vec<TokenStream> {
     fields
         .iter()
@@ -350,11 +1421,13 @@ fn accessors(fields: &Fields<StructLike>) -> Vec<TokenStream> {
         .collect()
 }
 
-#[proc_macro_derive(Diffable)]
+#[proc_macro_derive(Diffable, attributes(diffable))]
 pub fn derive_diffable(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast: DeriveInput = syn::parse(tokens).unwrap();
-    let diff = DeriveDiffable::from_derive_input(&ast).unwrap();
-    quote! { #diff }.into()
+    match DeriveDiffable::from_derive_input(&ast) {
+        Ok(diff) => quote! { #diff }.into(),
+        Err(e) => e.write_errors().into(),
+    }
 }
 
 #[cfg(test)]
@@ -377,16 +1450,21 @@ mod tests {
 
         let expect = quote! {
         #[derive(Debug, Clone, PartialEq)]
+        #[allow(non_camel_case_types)]
+        #[allow(non_snake_case)]
+        #[allow(dead_code)]
+        #[automatically_derived]
         struct SimpleStructDiff<'a> {
             x: <i32 as difficient::Diffable<'a>>::Diff,
             y: <String as difficient::Diffable<'a>>::Diff,
         }
         impl<'a> difficient::Diffable<'a> for SimpleStruct {
             type Diff = difficient::DeepDiff<'a, Self, SimpleStructDiff<'a>>;
+            #[allow(non_snake_case)]
             fn diff(&self, other: &'a Self) -> Self::Diff {
                 use difficient::Replace as _;
-                let x = self.x.diff(&other.x);
-                let y = self.y.diff(&other.y);
+                let x = (&self.x).diff(&other.x);
+                let y = (&self.y).diff(&other.y);
                 if x.is_unchanged() && y.is_unchanged() && true {
                     difficient::DeepDiff::Unchanged
                 } else if x.is_replaced() && y.is_replaced() && true {
@@ -398,6 +1476,7 @@ mod tests {
         }
         impl<'a> difficient::Apply for SimpleStructDiff<'a> {
             type Parent = SimpleStruct;
+            #[allow(non_snake_case)]
             fn apply_to_base(
                 &self,
                 source: &mut Self::Parent,
@@ -407,8 +1486,163 @@ mod tests {
                 self.y.apply_to_base(&mut source.y, errs)
             }
         }
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[allow(non_camel_case_types)]
+        #[automatically_derived]
+        enum SimpleStructField {
+            X,
+            Y,
+        }
+        impl<'a> difficient::ChangedFields for SimpleStructDiff<'a> {
+            type Field = SimpleStructField;
+            fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+                use difficient::Replace as _;
+                std::iter::empty()
+                    .chain((!self.x.is_unchanged()).then_some(SimpleStructField::X))
+                    .chain((!self.y.is_unchanged()).then_some(SimpleStructField::Y))
+            }
+        }
         };
 
         assert_eq!(expect.to_string(), derived.to_string());
     }
+
+    #[test]
+    fn test_diff_derive_and_attr() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(diff_derive(Eq, serde::Serialize), diff_attr(serde(rename_all = \"camelCase\")))]
+        enum SimpleEnum {
+            First(i32),
+        }
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let attrs = diff.diff_ty_attrs();
+
+        let expect = quote! {
+            #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+            #[serde(rename_all = "camelCase")]
+        };
+
+        assert_eq!(expect.to_string(), attrs.to_string());
+    }
+
+    #[test]
+    fn test_diff_name_and_crate() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(diff_name = \"UnitPatch\", crate = \"my_prelude::difficient\")]
+        struct Unit;
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let derived = quote! { #diff };
+
+        assert_eq!(diff.diff_ty().to_string(), "UnitPatch");
+        let expect = quote! {
+            impl<'a> my_prelude::difficient::Diffable<'a> for Unit {
+                type Diff = my_prelude::difficient::Id<Self>;
+                #[allow(non_snake_case)]
+                fn diff(&self, other: &'a Self) -> Self::Diff {
+                    my_prelude::difficient::Id::new()
+                }
+            }
+        };
+        assert_eq!(expect.to_string(), derived.to_string());
+    }
+
+    #[test]
+    fn test_field_enum_collision() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(diff_name = \"Patch\")]
+        struct Colliding {
+            foo_bar: i32,
+            fooBar: i32,
+        }
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let derived = quote! { #diff }.to_string();
+
+        assert!(derived.contains("compile_error"));
+        assert!(derived.contains("`CollidingField::FooBar`"));
+        assert!(derived.contains("field `foo_bar`"));
+    }
+
+    #[test]
+    fn test_remote_patterns() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(remote = \"vendor::Event\")]
+        enum EventDef {
+            Pair(u32, u32),
+            #[non_exhaustive]
+            Open { x: u32 },
+        }
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let Data::Enum(variants) = &diff.data else {
+            unreachable!()
+        };
+        let patterns = variants
+            .iter()
+            .map(|var| pattern_match(&var.fields, "left", diff.remote_rest(&var.attrs)).to_string())
+            .collect::<Vec<_>>();
+
+        // only the foreign #[non_exhaustive] variant may leave out fields
+        let expect = [
+            quote! { (left_0, left_1,) }.to_string(),
+            quote! { { x: left_x, .. } }.to_string(),
+        ];
+        assert_eq!(patterns, expect);
+    }
+
+    #[test]
+    fn test_mask_compares_without_diffing() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(mask, fields = false)]
+        struct Masked {
+            #[diffable(epsilon = 0.5)]
+            x: f64,
+            tags: HashMap<String, u32>,
+        }
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let Data::Struct(fields) = &diff.data else {
+            unreachable!()
+        };
+        let mask = diff.mask_impl(fields).to_string();
+
+        assert!(!mask.contains(". diff (") && !mask.contains("Diffable"));
+        assert!(mask.contains(&quote! { !(&self.tags == &other.tags) }.to_string()));
+        assert!(mask.contains("approx_eq (& self . x , & other . x"));
+    }
+
+    #[test]
+    fn test_atomic_unused_attrs() {
+        let input = "
+        #[derive(Diffable)]
+        #[diffable(atomic, eq_first)]
+        struct Atomic {
+            x: i32,
+        }
+        ";
+
+        let parsed = syn::parse_str(input).unwrap();
+        let diff = DeriveDiffable::from_derive_input(&parsed).unwrap();
+        let derived = quote! { #diff }.to_string();
+
+        assert!(derived.contains("compile_error"));
+        assert!(derived.contains("#[diffable(eq_first)] has no effect"));
+    }
 }
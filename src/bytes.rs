@@ -0,0 +1,14 @@
+use crate::{AtomicDiff, Diffable};
+use bytes::Bytes;
+
+impl<'a> Diffable<'a> for Bytes {
+    type Diff = AtomicDiff<'a, Self>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if self == other {
+            AtomicDiff::Unchanged
+        } else {
+            AtomicDiff::Replaced(other)
+        }
+    }
+}
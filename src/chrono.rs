@@ -8,7 +8,7 @@ impl<'a, Tz: chrono::TimeZone + 'a> Diffable<'a> for DateTime<Tz> {
         if self == other {
             AtomicDiff::Unchanged
         } else {
-            AtomicDiff::Replaced(&other)
+            AtomicDiff::Replaced(other)
         }
     }
 }
@@ -0,0 +1,153 @@
+//! Counters which diff as a delta rather than a replacement.
+//!
+//! Replacing a counter loses intent: if two replicas both increment it, the
+//! last writer wins and an increment is dropped. A `Counter` diffs as the
+//! change in value, and applying it adds the change onto whatever the value is
+//! now, so the increments of both replicas survive.
+//!
+//! Overflow is handled according to the counted type. Plain integers use
+//! checked arithmetic and report `ApplyError::Overflow`, while
+//! `std::num::Wrapping` integers wrap around.
+//!
+//! ```
+//! use difficient::{Diffable, counter::Counter};
+//!
+//! #[derive(Diffable, PartialEq, Debug, Clone)]
+//! struct Stats {
+//!     views: Counter<u64>,
+//! }
+//!
+//! let base = Stats { views: Counter(10) };
+//! let mine = Stats { views: Counter(11) };
+//! let mut theirs = Stats { views: Counter(12) };
+//! theirs.apply(base.diff(&mine)).unwrap();
+//! assert_eq!(theirs.views, Counter(13));
+//! ```
+
+use std::num::Wrapping;
+
+use crate::{Apply, ApplyError, DeepDiff, Diffable};
+
+/// A number which diffs as a delta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct Counter<T>(pub T);
+
+/// The change in the value of a counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CounterDiff<T> {
+    Increment(T),
+    Decrement(T),
+}
+
+/// The arithmetic a `Counter` needs from the type it counts
+pub trait Countable: Copy + PartialEq {
+    /// The delta from `from` to `to`, or `None` if it can't be represented
+    fn delta(from: Self, to: Self) -> Option<CounterDiff<Self>>;
+    /// Adjust `self` by `diff`, or `None` on overflow
+    fn adjust(self, diff: CounterDiff<Self>) -> Option<Self>;
+}
+
+macro_rules! countable_int {
+    ($($typ: ty)*) => ($(
+        impl Countable for $typ {
+            fn delta(from: Self, to: Self) -> Option<CounterDiff<Self>> {
+                if to >= from {
+                    to.checked_sub(from).map(CounterDiff::Increment)
+                } else {
+                    from.checked_sub(to).map(CounterDiff::Decrement)
+                }
+            }
+
+            fn adjust(self, diff: CounterDiff<Self>) -> Option<Self> {
+                match diff {
+                    CounterDiff::Increment(by) => self.checked_add(by),
+                    CounterDiff::Decrement(by) => self.checked_sub(by),
+                }
+            }
+        }
+
+        impl Countable for Wrapping<$typ> {
+            fn delta(from: Self, to: Self) -> Option<CounterDiff<Self>> {
+                Some(CounterDiff::Increment(to - from))
+            }
+
+            fn adjust(self, diff: CounterDiff<Self>) -> Option<Self> {
+                match diff {
+                    CounterDiff::Increment(by) => Some(self + by),
+                    CounterDiff::Decrement(by) => Some(self - by),
+                }
+            }
+        }
+    )*);
+}
+
+countable_int! {
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
+}
+
+macro_rules! countable_float {
+    ($($typ: ty)*) => ($(
+        impl Countable for $typ {
+            fn delta(from: Self, to: Self) -> Option<CounterDiff<Self>> {
+                if to >= from {
+                    Some(CounterDiff::Increment(to - from))
+                } else if to < from {
+                    Some(CounterDiff::Decrement(from - to))
+                } else {
+                    // NaN
+                    None
+                }
+            }
+
+            fn adjust(self, diff: CounterDiff<Self>) -> Option<Self> {
+                let adjusted = match diff {
+                    CounterDiff::Increment(by) => self + by,
+                    CounterDiff::Decrement(by) => self - by,
+                };
+                if adjusted.is_infinite() && self.is_finite() {
+                    None
+                } else {
+                    Some(adjusted)
+                }
+            }
+        }
+    )*);
+}
+
+countable_float! {
+    f32 f64
+}
+
+impl<'a, T> Diffable<'a> for Counter<T>
+where
+    T: Countable + 'a,
+{
+    type Diff = DeepDiff<'a, Self, CounterDiff<T>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if self == other {
+            return DeepDiff::Unchanged;
+        }
+        match T::delta(self.0, other.0) {
+            Some(delta) => DeepDiff::Patched(delta),
+            None => DeepDiff::Replaced(other),
+        }
+    }
+}
+
+impl<T> Apply for CounterDiff<T>
+where
+    T: Countable,
+{
+    type Parent = Counter<T>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        match source.0.adjust(*self) {
+            Some(adjusted) => source.0 = adjusted,
+            None => errs.push(ApplyError::Overflow),
+        }
+    }
+}
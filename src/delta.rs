@@ -0,0 +1,172 @@
+//! Binary deltas for byte buffers.
+//!
+//! Byte buffers are otherwise compared and replaced wholesale. The delta here
+//! finds runs shared with the base using a rolling hash, and describes the new
+//! buffer as copies from the base interleaved with inserted bytes. Use it as a
+//! field attribute:
+//!
+//! ```
+//! use difficient::{Diffable, DeepDiff};
+//!
+//! #[derive(Diffable, PartialEq, Debug, Clone)]
+//! struct Attachment {
+//!     #[diffable(with = "difficient::delta::Delta")]
+//!     data: Vec<u8>,
+//!     #[diffable(with = "difficient::delta::Delta<Box<[u8]>>")]
+//!     thumbnail: Box<[u8]>,
+//! }
+//! ```
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use crate::{Apply, ApplyError, DeepDiff, DiffableRemote};
+
+/// A single step in rebuilding the new buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ByteEdit<'a> {
+    Copy { offset: usize, len: usize },
+    Insert(&'a [u8]),
+}
+
+/// The steps which build the new buffer out of the base buffer, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ByteDelta<'a, B>(
+    pub Vec<ByteEdit<'a>>,
+    #[cfg_attr(feature = "serde", serde(skip))] PhantomData<B>,
+);
+
+impl<'a, B> ByteDelta<'a, B> {
+    pub fn new(edits: Vec<ByteEdit<'a>>) -> Self {
+        ByteDelta(edits, PhantomData)
+    }
+}
+
+impl<B> Apply for ByteDelta<'_, B>
+where
+    B: AsRef<[u8]> + From<Vec<u8>>,
+{
+    type Parent = B;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        let base = source.as_ref();
+        let mut out = Vec::with_capacity(base.len());
+        for edit in &self.0 {
+            match *edit {
+                ByteEdit::Copy { offset, len } => {
+                    let Some(span) = base.get(offset..offset.saturating_add(len)) else {
+                        errs.push(ApplyError::MismatchingBase);
+                        return;
+                    };
+                    out.extend_from_slice(span);
+                }
+                ByteEdit::Insert(bytes) => out.extend_from_slice(bytes),
+            }
+        }
+        *source = B::from(out);
+    }
+}
+
+/// Diff a byte buffer `B` (by default `Vec<u8>`) as a binary delta
+pub struct Delta<B = Vec<u8>>(PhantomData<B>);
+
+impl<'a, B> DiffableRemote<'a> for Delta<B>
+where
+    B: AsRef<[u8]> + From<Vec<u8>> + Clone + 'a,
+{
+    type Remote = B;
+    type Diff = DeepDiff<'a, B, ByteDelta<'a, B>>;
+
+    fn diff(this: &B, other: &'a B) -> Self::Diff {
+        if this.as_ref() == other.as_ref() {
+            return DeepDiff::Unchanged;
+        }
+        let edits = delta(this.as_ref(), other.as_ref());
+        if edits
+            .iter()
+            .any(|edit| matches!(edit, ByteEdit::Copy { .. }))
+        {
+            DeepDiff::Patched(ByteDelta::new(edits))
+        } else {
+            DeepDiff::Replaced(other)
+        }
+    }
+}
+
+const PRIME: u64 = 0x100000001b3;
+
+fn hash(block: &[u8]) -> u64 {
+    block
+        .iter()
+        .fold(0, |h, &b| h.wrapping_mul(PRIME).wrapping_add(b as u64))
+}
+
+fn delta<'a>(base: &[u8], new: &'a [u8]) -> Vec<ByteEdit<'a>> {
+    // larger buffers get larger blocks, to keep the index small
+    let block = base.len().isqrt().clamp(8, 4096);
+    if base.len() < block || new.len() < block {
+        return vec![ByteEdit::Insert(new)];
+    }
+    let mut index = HashMap::new();
+    for offset in (0..=base.len() - block).step_by(block) {
+        index
+            .entry(hash(&base[offset..offset + block]))
+            .or_insert(offset);
+    }
+    // the weight of the byte leaving the window
+    let outgoing = (1..block).fold(1u64, |p, _| p.wrapping_mul(PRIME));
+
+    let mut edits = Vec::new();
+    let mut literal = 0;
+    let mut pos = 0;
+    let mut h = hash(&new[..block]);
+    while pos + block <= new.len() {
+        let found = index
+            .get(&h)
+            .copied()
+            .filter(|&offset| base[offset..offset + block] == new[pos..pos + block]);
+        if let Some(mut offset) = found {
+            // grow the match backwards into the pending literal, then forwards
+            let mut start = pos;
+            while start > literal && offset > 0 && base[offset - 1] == new[start - 1] {
+                offset -= 1;
+                start -= 1;
+            }
+            let mut len = pos + block - start;
+            while offset + len < base.len()
+                && start + len < new.len()
+                && base[offset + len] == new[start + len]
+            {
+                len += 1;
+            }
+            if start > literal {
+                edits.push(ByteEdit::Insert(&new[literal..start]));
+            }
+            match edits.last_mut() {
+                Some(ByteEdit::Copy {
+                    offset: prev,
+                    len: prev_len,
+                }) if *prev + *prev_len == offset => *prev_len += len,
+                _ => edits.push(ByteEdit::Copy { offset, len }),
+            }
+            pos = start + len;
+            literal = pos;
+            if pos + block <= new.len() {
+                h = hash(&new[pos..pos + block]);
+            }
+            continue;
+        }
+        if pos + block < new.len() {
+            h = h
+                .wrapping_sub((new[pos] as u64).wrapping_mul(outgoing))
+                .wrapping_mul(PRIME)
+                .wrapping_add(new[pos + block] as u64);
+        }
+        pos += 1;
+    }
+    if literal < new.len() {
+        edits.push(ByteEdit::Insert(&new[literal..]));
+    }
+    edits
+}
@@ -0,0 +1,244 @@
+//! Diffs of arbitrary `Serialize` types, without a derive.
+//!
+//! Both sides are serialized into a `serde_json::Value` tree and diffed
+//! structurally. The result owns its data, and addresses each change by its
+//! path from the root. This is much slower than a derived diff, but works for
+//! any type which round-trips through serde.
+//!
+//! ```
+//! use difficient::{apply_serde, serde_diff};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+//! struct Legacy {
+//!     id: u32,
+//!     tags: Vec<String>,
+//! }
+//!
+//! let mut first = Legacy { id: 1, tags: vec!["a".into()] };
+//! let second = Legacy { id: 1, tags: vec!["a".into(), "b".into()] };
+//! let diff = serde_diff(&first, &second).unwrap();
+//! apply_serde(&mut first, &diff).unwrap();
+//! assert_eq!(first, second);
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{json::ValueDiff, ApplyError, DeepDiff, Diffable, KvDiff, VecDiff};
+
+/// One step along the path to a value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A single change, at the end of `path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DynChange {
+    /// Set the value, inserting it if it is a new key
+    Set {
+        path: Vec<PathSegment>,
+        value: Value,
+    },
+    /// Remove the key
+    Remove { path: Vec<PathSegment> },
+    /// Append to the array
+    Append {
+        path: Vec<PathSegment>,
+        values: Vec<Value>,
+    },
+    /// Truncate the array
+    Truncate { path: Vec<PathSegment>, len: usize },
+}
+
+/// The changes between two serialized values, in the order they apply
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DynDiff(pub Vec<DynChange>);
+
+impl DynDiff {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum SerdeDiffError {
+    Serde(serde_json::Error),
+    Apply(Vec<ApplyError>),
+}
+
+impl std::fmt::Display for SerdeDiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerdeDiffError::Serde(err) => write!(f, "serde error: {err}"),
+            SerdeDiffError::Apply(errs) => {
+                write!(f, "failed to apply diff:")?;
+                for err in errs {
+                    write!(f, " {err};")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SerdeDiffError {}
+
+impl From<serde_json::Error> for SerdeDiffError {
+    fn from(err: serde_json::Error) -> Self {
+        SerdeDiffError::Serde(err)
+    }
+}
+
+/// Diff two values through their `Serialize` impls
+pub fn serde_diff<T: Serialize>(this: &T, other: &T) -> Result<DynDiff, serde_json::Error> {
+    let this = serde_json::to_value(this)?;
+    let other = serde_json::to_value(other)?;
+    let mut changes = Vec::new();
+    flatten(&this.diff(&other), &mut Vec::new(), &mut changes);
+    Ok(DynDiff(changes))
+}
+
+/// Apply a diff from `serde_diff`, round-tripping `target` through serde
+pub fn apply_serde<T>(target: &mut T, diff: &DynDiff) -> Result<(), SerdeDiffError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut value = serde_json::to_value(&*target)?;
+    let mut errs = Vec::new();
+    for change in &diff.0 {
+        apply_change(&mut value, change, &mut errs);
+    }
+    if !errs.is_empty() {
+        return Err(SerdeDiffError::Apply(errs));
+    }
+    *target = serde_json::from_value(value)?;
+    Ok(())
+}
+
+fn flatten(
+    diff: &DeepDiff<'_, Value, ValueDiff<'_>>,
+    path: &mut Vec<PathSegment>,
+    out: &mut Vec<DynChange>,
+) {
+    match diff {
+        DeepDiff::Unchanged => {}
+        DeepDiff::Replaced(value) => out.push(DynChange::Set {
+            path: path.clone(),
+            value: (*value).clone(),
+        }),
+        DeepDiff::Patched(ValueDiff::Object(diffs)) => {
+            for (key, diff) in diffs {
+                path.push(PathSegment::Key(key.clone()));
+                match diff {
+                    KvDiff::Removed => out.push(DynChange::Remove { path: path.clone() }),
+                    KvDiff::Inserted(value) => out.push(DynChange::Set {
+                        path: path.clone(),
+                        value: (*value).clone(),
+                    }),
+                    KvDiff::Diff(diff) => flatten(diff, path, out),
+                }
+                path.pop();
+            }
+        }
+        DeepDiff::Patched(ValueDiff::Array { elems, resize }) => {
+            for (ix, diff) in elems {
+                path.push(PathSegment::Index(*ix));
+                flatten(diff, path, out);
+                path.pop();
+            }
+            match resize {
+                Some(VecDiff::Appended(values)) => out.push(DynChange::Append {
+                    path: path.clone(),
+                    values: values.to_vec(),
+                }),
+                Some(VecDiff::Truncated(len)) => out.push(DynChange::Truncate {
+                    path: path.clone(),
+                    len: *len,
+                }),
+                None => {}
+            }
+        }
+    }
+}
+
+fn lookup<'v>(
+    mut value: &'v mut Value,
+    path: &[PathSegment],
+    errs: &mut Vec<ApplyError>,
+) -> Option<&'v mut Value> {
+    for segment in path {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Value::Object(map)) => match map.get_mut(key) {
+                Some(value) => value,
+                None => {
+                    errs.push(ApplyError::MissingKey);
+                    return None;
+                }
+            },
+            (PathSegment::Index(ix), Value::Array(vec)) => match vec.get_mut(*ix) {
+                Some(value) => value,
+                None => {
+                    errs.push(ApplyError::MismatchingBase);
+                    return None;
+                }
+            },
+            _ => {
+                errs.push(ApplyError::MismatchingEnum);
+                return None;
+            }
+        };
+    }
+    Some(value)
+}
+
+fn apply_change(root: &mut Value, change: &DynChange, errs: &mut Vec<ApplyError>) {
+    match change {
+        DynChange::Set { path, value } => {
+            let Some((last, parent)) = path.split_last() else {
+                *root = value.clone();
+                return;
+            };
+            match (last, lookup(root, parent, errs)) {
+                (_, None) => {}
+                (PathSegment::Key(key), Some(Value::Object(map))) => {
+                    map.insert(key.clone(), value.clone());
+                }
+                (PathSegment::Index(ix), Some(Value::Array(vec))) => match vec.get_mut(*ix) {
+                    Some(elem) => *elem = value.clone(),
+                    None => errs.push(ApplyError::MismatchingBase),
+                },
+                _ => errs.push(ApplyError::MismatchingEnum),
+            }
+        }
+        DynChange::Remove { path } => {
+            let Some((PathSegment::Key(key), parent)) = path.split_last() else {
+                errs.push(ApplyError::MismatchingEnum);
+                return;
+            };
+            match lookup(root, parent, errs) {
+                None => {}
+                Some(Value::Object(map)) => match map.remove(key) {
+                    Some(_) => {}
+                    None => errs.push(ApplyError::MissingKey),
+                },
+                Some(_) => errs.push(ApplyError::MismatchingEnum),
+            }
+        }
+        DynChange::Append { path, values } => match lookup(root, path, errs) {
+            None => {}
+            Some(Value::Array(vec)) => vec.extend(values.iter().cloned()),
+            Some(_) => errs.push(ApplyError::MismatchingEnum),
+        },
+        DynChange::Truncate { path, len } => match lookup(root, path, errs) {
+            None => {}
+            Some(Value::Array(vec)) if *len <= vec.len() => vec.truncate(*len),
+            Some(Value::Array(_)) => errs.push(ApplyError::MismatchingBase),
+            Some(_) => errs.push(ApplyError::MismatchingEnum),
+        },
+    }
+}
@@ -0,0 +1,316 @@
+//! Diffs of `IndexMap` and `IndexSet` which keep track of their order.
+//!
+//! Entries are diffed by key like those of a `HashMap`, and the patch also
+//! holds the moves which put the keys into their new order. Only keys which
+//! are out of place are moved: the longest run of keys which are already in
+//! order stays put.
+//!
+//! ```
+//! use difficient::Diffable;
+//! use indexmap::IndexMap;
+//!
+//! let mut columns: IndexMap<&str, u32> = [("id", 4), ("name", 20)].into_iter().collect();
+//! let reordered: IndexMap<&str, u32> = [("name", 20), ("id", 4)].into_iter().collect();
+//! let diff = columns.diff(&reordered);
+//! columns.apply(diff).unwrap();
+//! assert!(columns.keys().eq(reordered.keys()));
+//! ```
+
+use std::{collections::HashMap, hash::Hash};
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{apply_kv, diff_kv, Apply, ApplyError, DeepDiff, Diffable, KvDiff, KvMap};
+
+/// Moves which reorder an indexed collection, in the order they apply.
+/// Each `(from, to)` moves the element at `from` so that it ends up at `to`
+pub type Moves = Vec<(usize, usize)>;
+
+/// The patch of an `IndexMap`.
+///
+/// `entries` apply in order: insertions go at the end, after the removals
+/// and changed values, which come first. Finally the moves put the keys into
+/// their new order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IndexMapDiff<K: Hash + Eq, D> {
+    pub entries: IndexMap<K, D>,
+    pub moves: Moves,
+}
+
+/// The patch of an `IndexSet`.
+///
+/// Removals apply first, then insertions at the end, and finally the moves
+/// which put the elements into their new order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IndexSetDiff<'a, T> {
+    pub removed: Vec<T>,
+    pub inserted: Vec<&'a T>,
+    pub moves: Moves,
+}
+
+impl<K, V> KvMap for IndexMap<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    type Key = K;
+    type Value = V;
+
+    fn kv_iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.iter()
+    }
+
+    fn kv_get(&self, k: &K) -> Option<&V> {
+        self.get(k)
+    }
+
+    fn kv_get_mut(&mut self, k: &K) -> Option<&mut V> {
+        self.get_mut(k)
+    }
+
+    fn kv_insert(&mut self, k: K, v: V) -> Option<V> {
+        self.insert(k, v)
+    }
+
+    fn kv_remove(&mut self, k: &K) -> Option<V> {
+        self.shift_remove(k)
+    }
+}
+
+impl<'a, K, V> Diffable<'a> for IndexMap<K, V>
+where
+    K: Hash + Eq + Clone + 'a,
+    V: Diffable<'a> + Clone + 'a,
+{
+    type Diff = DeepDiff<'a, Self, IndexMapDiff<K, KvDiff<'a, V>>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        let entries = match diff_kv(self, other, other, |entries| entries) {
+            DeepDiff::Unchanged => IndexMap::new(),
+            DeepDiff::Patched(entries) => entries,
+            DeepDiff::Replaced(other) => return DeepDiff::Replaced(other),
+        };
+        // the order of the keys once the entries have been applied
+        let order: Vec<&K> = self
+            .keys()
+            .filter(|k| other.contains_key(*k))
+            .chain(other.keys().filter(|k| !self.contains_key(*k)))
+            .collect();
+        let moves = moves(order, other.keys().collect());
+        if entries.is_empty() && moves.is_empty() {
+            DeepDiff::Unchanged
+        } else {
+            DeepDiff::Patched(IndexMapDiff { entries, moves })
+        }
+    }
+}
+
+impl<'a, K, V> Apply for IndexMapDiff<K, KvDiff<'a, V>>
+where
+    K: Hash + Eq + Clone,
+    V: Diffable<'a> + Clone,
+{
+    type Parent = IndexMap<K, V>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        // removals keep the order of the other keys, and insertions go at the end
+        apply_kv(&self.entries, source, errs);
+        apply_moves(
+            &self.moves,
+            source.len(),
+            |from, to| source.move_index(from, to),
+            errs,
+        );
+    }
+}
+
+impl<'a, T> Diffable<'a> for IndexSet<T>
+where
+    T: Hash + Eq + Clone + 'a,
+{
+    type Diff = DeepDiff<'a, Self, IndexSetDiff<'a, T>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        let removed: Vec<T> = self.difference(other).cloned().collect();
+        let inserted: Vec<&T> = other.iter().filter(|elem| !self.contains(*elem)).collect();
+        if removed.len() == self.len() && !self.is_empty() {
+            return DeepDiff::Replaced(other);
+        }
+        let order: Vec<&T> = self
+            .iter()
+            .filter(|elem| other.contains(*elem))
+            .chain(inserted.iter().copied())
+            .collect();
+        let moves = moves(order, other.iter().collect());
+        if removed.is_empty() && inserted.is_empty() && moves.is_empty() {
+            DeepDiff::Unchanged
+        } else {
+            DeepDiff::Patched(IndexSetDiff {
+                removed,
+                inserted,
+                moves,
+            })
+        }
+    }
+}
+
+impl<T> Apply for IndexSetDiff<'_, T>
+where
+    T: Hash + Eq + Clone,
+{
+    type Parent = IndexSet<T>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        for elem in &self.removed {
+            if !source.shift_remove(elem) {
+                errs.push(ApplyError::MissingKey);
+            }
+        }
+        for &elem in &self.inserted {
+            if !source.insert(elem.clone()) {
+                errs.push(ApplyError::UnexpectedKey);
+            }
+        }
+        apply_moves(
+            &self.moves,
+            source.len(),
+            |from, to| source.move_index(from, to),
+            errs,
+        );
+    }
+}
+
+fn apply_moves(
+    moves: &Moves,
+    len: usize,
+    mut move_index: impl FnMut(usize, usize),
+    errs: &mut Vec<ApplyError>,
+) {
+    for &(from, to) in moves {
+        if from < len && to < len {
+            move_index(from, to);
+        } else {
+            errs.push(ApplyError::MismatchingBase);
+        }
+    }
+}
+
+/// The moves which turn `current` into `target`, which must hold the same keys.
+///
+/// The longest run of keys which are already in order stays put, and every
+/// other key is moved to just after its predecessor in `target`
+fn moves<K: Hash + Eq>(current: Vec<&K>, target: Vec<&K>) -> Moves {
+    let position: HashMap<&K, usize> = target.iter().enumerate().map(|(ix, k)| (*k, ix)).collect();
+    let ranks: Vec<usize> = current.iter().map(|k| position[k]).collect();
+    let mut stays = vec![false; target.len()];
+    for rank in longest_increasing(&ranks) {
+        stays[rank] = true;
+    }
+    // where each key is in `current`, by its rank in `target`
+    let mut original = vec![0; target.len()];
+    for (ix, &rank) in ranks.iter().enumerate() {
+        original[rank] = ix;
+    }
+
+    // Every key gets a slot in one fixed order, and its index is the number of
+    // occupied slots before its own. A key starts in slot `(ix + 1, 0)`. Moving
+    // puts it in `(anchor, offset)`: after the key which stays and precedes it in
+    // `target` (or the front, as anchor 0), and after the keys moved there before it
+    let start = |ix: usize| (ix + 1, 0);
+    let mut moved_to = vec![None; target.len()];
+    let (mut anchor, mut offset) = (0, 0);
+    for (rank, slot) in moved_to.iter_mut().enumerate() {
+        if stays[rank] {
+            (anchor, offset) = (start(original[rank]).0, 0);
+        } else {
+            offset += 1;
+            *slot = Some((anchor, offset));
+        }
+    }
+    let mut slots: Vec<(usize, usize)> = (0..current.len())
+        .map(start)
+        .chain(moved_to.iter().flatten().copied())
+        .collect();
+    slots.sort_unstable();
+    let slot_ix = |slot: (usize, usize)| slots.binary_search(&slot).unwrap();
+
+    let mut occupied = Occupied::new(slots.len());
+    for ix in 0..current.len() {
+        occupied.insert(slot_ix(start(ix)));
+    }
+    let mut moves = Vec::new();
+    for (rank, slot) in moved_to.into_iter().enumerate() {
+        let Some(slot) = slot else {
+            continue;
+        };
+        let (old, new) = (slot_ix(start(original[rank])), slot_ix(slot));
+        let from = occupied.count_before(old);
+        occupied.remove(old);
+        let to = occupied.count_before(new);
+        occupied.insert(new);
+        if from != to {
+            moves.push((from, to));
+        }
+    }
+    moves
+}
+
+/// A set of slots which counts the occupied slots before any slot in
+/// logarithmic time (a Fenwick tree)
+struct Occupied(Vec<isize>);
+
+impl Occupied {
+    fn new(len: usize) -> Self {
+        Occupied(vec![0; len + 1])
+    }
+
+    fn add(&mut self, slot: usize, delta: isize) {
+        let mut ix = slot + 1;
+        while ix < self.0.len() {
+            self.0[ix] += delta;
+            ix += ix & ix.wrapping_neg();
+        }
+    }
+
+    fn insert(&mut self, slot: usize) {
+        self.add(slot, 1)
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.add(slot, -1)
+    }
+
+    fn count_before(&self, slot: usize) -> usize {
+        let (mut ix, mut count) = (slot, 0);
+        while ix > 0 {
+            count += self.0[ix];
+            ix &= ix - 1;
+        }
+        count as usize
+    }
+}
+
+/// The values of a longest strictly increasing subsequence
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[len] is the index of the smallest value ending an increasing run of length len + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; values.len()];
+    for (ix, &value) in values.iter().enumerate() {
+        let len = tails.partition_point(|&tail| values[tail] < value);
+        prev[ix] = len.checked_sub(1).map(|len| tails[len]);
+        if len == tails.len() {
+            tails.push(ix);
+        } else {
+            tails[len] = ix;
+        }
+    }
+    let mut run = Vec::new();
+    let mut cursor = tails.last().copied();
+    while let Some(ix) = cursor {
+        run.push(values[ix]);
+        cursor = prev[ix];
+    }
+    run.reverse();
+    run
+}
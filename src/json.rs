@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    apply_by_index, apply_kv, diff_by_index, diff_kv, Apply, ApplyError, DeepDiff, Diffable,
+    KvDiff, KvMap, VecDiff,
+};
+
+/// The patch of a JSON object or array. Scalars, and values which change
+/// kind, are replaced outright
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ValueDiff<'a> {
+    Object(BTreeMap<String, KvDiff<'a, Value>>),
+    Array {
+        /// Patches to the elements the arrays have in common, by index
+        elems: BTreeMap<usize, DeepDiff<'a, Value, ValueDiff<'a>>>,
+        /// How the end of the array changed, if its length did
+        resize: Option<VecDiff<'a, Value>>,
+    },
+}
+
+impl<'a> Diffable<'a> for Value {
+    type Diff = DeepDiff<'a, Self, ValueDiff<'a>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        match (self, other) {
+            (Value::Object(left), Value::Object(right)) => diff_object(left, right, other),
+            (Value::Array(left), Value::Array(right)) => diff_array(left, right, other),
+            _ if self == other => DeepDiff::Unchanged,
+            _ => DeepDiff::Replaced(other),
+        }
+    }
+}
+
+impl KvMap for Map<String, Value> {
+    type Key = String;
+    type Value = Value;
+
+    fn kv_iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.iter()
+    }
+
+    fn kv_get(&self, k: &String) -> Option<&Value> {
+        self.get(k)
+    }
+
+    fn kv_get_mut(&mut self, k: &String) -> Option<&mut Value> {
+        self.get_mut(k)
+    }
+
+    fn kv_insert(&mut self, k: String, v: Value) -> Option<Value> {
+        self.insert(k, v)
+    }
+
+    fn kv_remove(&mut self, k: &String) -> Option<Value> {
+        self.remove(k)
+    }
+}
+
+fn diff_object<'a>(
+    left: &Map<String, Value>,
+    right: &'a Map<String, Value>,
+    other: &'a Value,
+) -> DeepDiff<'a, Value, ValueDiff<'a>> {
+    diff_kv(left, right, other, ValueDiff::Object)
+}
+
+fn diff_array<'a>(
+    left: &[Value],
+    right: &'a [Value],
+    other: &'a Value,
+) -> DeepDiff<'a, Value, ValueDiff<'a>> {
+    let common = left.len().min(right.len());
+    let resize = if right.len() > left.len() {
+        Some(VecDiff::Appended(&right[left.len()..]))
+    } else if right.len() < left.len() {
+        Some(VecDiff::Truncated(right.len()))
+    } else {
+        None
+    };
+    match diff_by_index(&left[..common], &right[..common], other, |elems| elems) {
+        DeepDiff::Unchanged if resize.is_none() => DeepDiff::Unchanged,
+        DeepDiff::Unchanged if common > 0 => DeepDiff::Patched(ValueDiff::Array {
+            elems: BTreeMap::new(),
+            resize,
+        }),
+        DeepDiff::Patched(elems) => DeepDiff::Patched(ValueDiff::Array { elems, resize }),
+        // nothing is kept
+        _ => DeepDiff::Replaced(other),
+    }
+}
+
+impl Apply for ValueDiff<'_> {
+    type Parent = Value;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        match (self, source) {
+            (ValueDiff::Object(diffs), Value::Object(map)) => apply_kv(diffs, map, errs),
+            (ValueDiff::Array { elems, resize }, Value::Array(vec)) => {
+                apply_by_index(elems, vec, errs);
+                if let Some(resize) = resize {
+                    resize.apply_to_base(vec, errs);
+                }
+            }
+            _ => errs.push(ApplyError::MismatchingEnum),
+        }
+    }
+}
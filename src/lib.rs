@@ -59,16 +59,43 @@
 #![deny(warnings)]
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    borrow::Cow,
+    cmp,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, LinkedList, VecDeque},
+    ffi,
     hash::Hash,
     marker::PhantomData,
-    ops::Deref,
+    net, num,
+    num::Wrapping,
+    ops::{Deref, Range},
+    path,
+    rc::Rc,
+    sync::Arc,
+    time,
 };
 
 pub use difficient_macros::Diffable;
+#[cfg(feature = "serde_json")]
+pub use dynamic::{apply_serde, serde_diff, DynChange, DynDiff, PathSegment, SerdeDiffError};
 
+#[cfg(feature = "bytes")]
+mod bytes;
 #[cfg(feature = "chrono")]
 mod chrono;
+pub mod counter;
+pub mod delta;
+#[cfg(feature = "serde_json")]
+mod dynamic;
+#[cfg(feature = "indexmap")]
+pub mod indexmap;
+#[cfg(feature = "serde_json")]
+pub mod json;
+#[cfg(feature = "bytemuck")]
+pub mod pod;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod tolerance;
 #[cfg(feature = "uuid")]
 mod uuid;
 
@@ -88,6 +115,26 @@ pub trait Diffable<'a>: Sized {
     }
 }
 
+/// Diff a foreign type through a local mirror of its definition.
+///
+/// `#[derive(Diffable)]` with `#[diffable(remote = "other_crate::Config")]` implements this
+/// for the mirror type. Fields of the foreign type can then be diffed in other derived types
+/// with `#[diffable(with = "ConfigDef")]`.
+pub trait DiffableRemote<'a> {
+    type Remote;
+    type Diff: Replace<Replaces = Self::Remote> + Apply<Parent = Self::Remote>;
+    fn diff(this: &Self::Remote, other: &'a Self::Remote) -> Self::Diff;
+    fn apply(this: &mut Self::Remote, diff: Self::Diff) -> Result<(), Vec<ApplyError>> {
+        let mut errs = Vec::new();
+        diff.apply_to_base(this, &mut errs);
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+}
+
 pub trait Replace {
     type Replaces;
     fn is_unchanged(&self) -> bool;
@@ -99,11 +146,32 @@ pub trait Apply {
     fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>);
 }
 
+/// A diff which can be constructed directly, without diffing two values.
+///
+/// Used to convert the `FooPartial` types generated by `#[diffable(partial)]` into diffs.
+pub trait Replacement<'a>: Replace {
+    fn unchanged() -> Self;
+    fn replaced_by(value: &'a Self::Replaces) -> Self;
+}
+
+/// A diff which can report the paths of the fields it changes.
+///
+/// `#[derive(Diffable)]` implements this for the diff type of a struct `Foo`, yielding
+/// variants of a generated `FooField` enum (opt out with `#[diffable(fields = false)]`).
+/// Fields marked `#[diffable(nested)]` yield the changed paths of the child as well;
+/// the derive can't see whether a field's type is itself derived, so this is explicit.
+pub trait ChangedFields {
+    type Field;
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_;
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ApplyError {
     MismatchingEnum,
     MissingKey,
     UnexpectedKey,
+    MismatchingBase,
+    Overflow,
 }
 
 impl std::fmt::Display for ApplyError {
@@ -118,6 +186,12 @@ impl std::fmt::Display for ApplyError {
             ApplyError::UnexpectedKey => {
                 write!(f, "unexpected key")
             }
+            ApplyError::MismatchingBase => {
+                write!(f, "value does not match the base of the diff")
+            }
+            ApplyError::Overflow => {
+                write!(f, "arithmetic overflow")
+            }
         }
     }
 }
@@ -126,7 +200,8 @@ impl std::error::Error for ApplyError {}
 
 // *** Helper structs
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Id<T>(PhantomData<T>);
 
 impl<T> Id<T> {
@@ -135,6 +210,12 @@ impl<T> Id<T> {
     }
 }
 
+impl<T> Default for Id<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Replace for Id<T> {
     type Replaces = T;
 
@@ -147,12 +228,23 @@ impl<T> Replace for Id<T> {
     }
 }
 
+impl<'a, T> Replacement<'a> for Id<T> {
+    fn unchanged() -> Self {
+        Id::new()
+    }
+
+    fn replaced_by(_: &'a T) -> Self {
+        Id::new()
+    }
+}
+
 impl<T> Apply for Id<T> {
     type Parent = T;
     fn apply_to_base(&self, _: &mut Self::Parent, _: &mut Vec<ApplyError>) {}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AtomicDiff<'a, T> {
     Unchanged,
     Replaced(&'a T),
@@ -170,6 +262,16 @@ impl<'a, T> Replace for AtomicDiff<'a, T> {
     }
 }
 
+impl<'a, T> Replacement<'a> for AtomicDiff<'a, T> {
+    fn unchanged() -> Self {
+        AtomicDiff::Unchanged
+    }
+
+    fn replaced_by(value: &'a T) -> Self {
+        AtomicDiff::Replaced(value)
+    }
+}
+
 impl<'a, T> Apply for AtomicDiff<'a, T>
 where
     T: Clone,
@@ -183,7 +285,8 @@ where
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DeepDiff<'a, Full, Patch> {
     Unchanged,
     Patched(Patch),
@@ -202,9 +305,19 @@ impl<'a, T, U> Replace for DeepDiff<'a, T, U> {
     }
 }
 
+impl<'a, T, U> Replacement<'a> for DeepDiff<'a, T, U> {
+    fn unchanged() -> Self {
+        DeepDiff::Unchanged
+    }
+
+    fn replaced_by(value: &'a T) -> Self {
+        DeepDiff::Replaced(value)
+    }
+}
+
 impl<'a, T, U> Apply for DeepDiff<'a, T, U>
 where
-    T: Diffable<'a> + Clone,
+    T: Clone,
     U: Apply<Parent = T>, // <<T as Diffable>::Diff as Apply>::Parent = T,
 {
     type Parent = T;
@@ -218,7 +331,102 @@ where
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// A nested path is `None` if the whole value was replaced
+impl<'a, T, U> ChangedFields for DeepDiff<'a, T, U>
+where
+    U: ChangedFields,
+{
+    type Field = Option<U::Field>;
+
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+        let (replaced, patched) = match self {
+            DeepDiff::Unchanged => (None, None),
+            DeepDiff::Patched(patch) => (None, Some(patch)),
+            DeepDiff::Replaced(_) => (Some(None), None),
+        };
+        replaced.into_iter().chain(
+            patched
+                .into_iter()
+                .flat_map(|p| p.changed_fields().map(Some)),
+        )
+    }
+}
+
+/// The diff of a field marked `#[diffable(via = "Proxy")]`.
+///
+/// The field is converted into the proxy type on both sides, and only the diff of the
+/// proxies is kept. Proxies are temporary, so that diff must not borrow from them: the
+/// proxy implements `Diffable<'b>` with the same `Diff` type for every `'b`. Applying
+/// converts the field into the proxy, patches it and converts it back.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ViaDiff<'a, T, P: Diffable<'static>> {
+    Unchanged,
+    Patched(P::Diff),
+    Replaced(&'a T),
+}
+
+impl<'a, T, P, D> ViaDiff<'a, T, P>
+where
+    P: for<'b> From<&'b T> + for<'b> Diffable<'b, Diff = D>,
+    D: Replace,
+{
+    pub fn new(this: &T, other: &'a T) -> Self {
+        let diff = P::from(this).diff(&P::from(other));
+        if diff.is_unchanged() {
+            ViaDiff::Unchanged
+        } else if diff.is_replaced() {
+            ViaDiff::Replaced(other)
+        } else {
+            ViaDiff::Patched(diff)
+        }
+    }
+}
+
+impl<'a, T, P: Diffable<'static>> Replace for ViaDiff<'a, T, P> {
+    type Replaces = T;
+
+    fn is_unchanged(&self) -> bool {
+        matches!(self, ViaDiff::Unchanged)
+    }
+
+    fn is_replaced(&self) -> bool {
+        matches!(self, ViaDiff::Replaced(_))
+    }
+}
+
+impl<'a, T, P: Diffable<'static>> Replacement<'a> for ViaDiff<'a, T, P> {
+    fn unchanged() -> Self {
+        ViaDiff::Unchanged
+    }
+
+    fn replaced_by(value: &'a T) -> Self {
+        ViaDiff::Replaced(value)
+    }
+}
+
+impl<'a, T, P> Apply for ViaDiff<'a, T, P>
+where
+    T: Clone + From<P>,
+    P: for<'b> From<&'b T> + Diffable<'static>,
+{
+    type Parent = T;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        match self {
+            ViaDiff::Unchanged => {}
+            ViaDiff::Patched(diff) => {
+                let mut proxy = P::from(&*source);
+                diff.apply_to_base(&mut proxy, errs);
+                *source = proxy.into();
+            }
+            ViaDiff::Replaced(r) => *source = (*r).clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum KvDiff<'a, T: Diffable<'a>> {
     Removed,
     Inserted(&'a T),
@@ -236,7 +444,7 @@ macro_rules! impl_diffable_for_primitives {
                 if self == other {
                     AtomicDiff::Unchanged
                 } else {
-                    AtomicDiff::Replaced(&other)
+                    AtomicDiff::Replaced(other)
                 }
             }
         }
@@ -244,32 +452,428 @@ macro_rules! impl_diffable_for_primitives {
 }
 
 impl_diffable_for_primitives! {
-    i8 i16 i32 i64
-    u8 u16 u32 u64
-    f32 f64
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
     bool
-    &'static str
+    char
     String
+    num::NonZeroI8 num::NonZeroI16 num::NonZeroI32 num::NonZeroI64 num::NonZeroI128 num::NonZeroIsize
+    num::NonZeroU8 num::NonZeroU16 num::NonZeroU32 num::NonZeroU64 num::NonZeroU128 num::NonZeroUsize
+    time::Duration time::SystemTime
+    path::PathBuf
+    ffi::OsString
+    net::IpAddr net::Ipv4Addr net::Ipv6Addr
+    net::SocketAddr net::SocketAddrV4 net::SocketAddrV6
+    cmp::Ordering
+}
+
+macro_rules! impl_diffable_for_floats {
+    ($($typ: ty)*) => ($(
+        impl<'a> Diffable<'a> for $typ {
+            type Diff = AtomicDiff<'a, Self>;
+
+            fn diff(&self, other: &'a Self) -> Self::Diff {
+                // NaN never equals itself, but it hasn't changed
+                if self == other || (self.is_nan() && other.is_nan()) {
+                    AtomicDiff::Unchanged
+                } else {
+                    AtomicDiff::Replaced(other)
+                }
+            }
+        }
+    )*);
+}
+
+impl_diffable_for_floats! {
+    f32 f64
+}
+
+impl<'a, 'b: 'a> Diffable<'a> for &'b str {
+    type Diff = AtomicDiff<'a, Self>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if self == other {
+            AtomicDiff::Unchanged
+        } else {
+            AtomicDiff::Replaced(other)
+        }
+    }
+}
+
+macro_rules! impl_diffable_for_atomic_wrappers {
+    ($($typ: ident)*) => ($(
+        impl<'a, T: Clone + PartialEq + 'a> Diffable<'a> for $typ<T> {
+            type Diff = AtomicDiff<'a, Self>;
+
+            fn diff(&self, other: &'a Self) -> Self::Diff {
+                if self == other {
+                    AtomicDiff::Unchanged
+                } else {
+                    AtomicDiff::Replaced(other)
+                }
+            }
+        }
+    )*);
+}
+
+impl_diffable_for_atomic_wrappers! {
+    Wrapping
+    Range
+    Reverse
+    LinkedList
+}
+
+/// The patch of a `Vec` which only grew or shrank at the end
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum VecDiff<'a, T> {
+    Appended(&'a [T]),
+    Truncated(usize),
 }
 
 impl<'a, T: Clone + PartialEq + 'a> Diffable<'a> for Vec<T> {
-    type Diff = AtomicDiff<'a, Vec<T>>;
+    type Diff = DeepDiff<'a, Vec<T>, VecDiff<'a, T>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if self.len() == other.len() {
+            if self == other {
+                DeepDiff::Unchanged
+            } else {
+                DeepDiff::Replaced(other)
+            }
+        } else if !self.is_empty() && other.starts_with(self) {
+            DeepDiff::Patched(VecDiff::Appended(&other[self.len()..]))
+        } else if self.starts_with(other) {
+            DeepDiff::Patched(VecDiff::Truncated(other.len()))
+        } else {
+            DeepDiff::Replaced(other)
+        }
+    }
+}
+
+impl<T: Clone> Apply for VecDiff<'_, T> {
+    type Parent = Vec<T>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        match *self {
+            VecDiff::Appended(tail) => source.extend_from_slice(tail),
+            VecDiff::Truncated(len) if len <= source.len() => source.truncate(len),
+            VecDiff::Truncated(_) => errs.push(ApplyError::MismatchingBase),
+        }
+    }
+}
+
+/// Diff two equal-length slices element by element, keyed by index.
+/// `whole` is the replacement used when every element was replaced.
+pub(crate) fn diff_by_index<'a, T, Full, Patch>(
+    this: &[T],
+    other: &'a [T],
+    whole: &'a Full,
+    patch: impl FnOnce(BTreeMap<usize, T::Diff>) -> Patch,
+) -> DeepDiff<'a, Full, Patch>
+where
+    T: Diffable<'a> + 'a,
+{
+    let mut diffs = BTreeMap::new();
+    let mut all_replaced = true;
+    for (ix, (elem, other_elem)) in this.iter().zip(other.iter()).enumerate() {
+        let diff = elem.diff(other_elem);
+        if diff.is_unchanged() {
+            all_replaced = false;
+        } else {
+            all_replaced &= diff.is_replaced();
+            diffs.insert(ix, diff);
+        }
+    }
+    if diffs.is_empty() {
+        DeepDiff::Unchanged
+    } else if all_replaced {
+        DeepDiff::Replaced(whole)
+    } else {
+        DeepDiff::Patched(patch(diffs))
+    }
+}
+
+/// Apply an index-keyed patch, reporting indices past the end as a mismatching base
+pub(crate) fn apply_by_index<T: Apply>(
+    diffs: &BTreeMap<usize, T>,
+    source: &mut [T::Parent],
+    errs: &mut Vec<ApplyError>,
+) {
+    for (ix, diff) in diffs {
+        match source.get_mut(*ix) {
+            Some(elem) => diff.apply_to_base(elem, errs),
+            None => errs.push(ApplyError::MismatchingBase),
+        }
+    }
+}
+
+/// Diff a `Vec` element by element, pairing up elements by position.
+///
+/// Use as `#[diffable(with = "difficient::Positional<Vec<T>>")]`. Vecs of equal
+/// length are patched at the indices which changed; otherwise the whole `Vec`
+/// is replaced.
+pub struct Positional<V>(PhantomData<V>);
+
+/// The element-wise patch of a `Vec`, keyed by index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PositionalDiff<T>(pub BTreeMap<usize, T>);
+
+impl<'a, T> DiffableRemote<'a> for Positional<Vec<T>>
+where
+    T: Diffable<'a> + Clone + 'a,
+{
+    type Remote = Vec<T>;
+    type Diff = DeepDiff<'a, Vec<T>, PositionalDiff<T::Diff>>;
+
+    fn diff(this: &Vec<T>, other: &'a Vec<T>) -> Self::Diff {
+        if this.len() != other.len() {
+            return DeepDiff::Replaced(other);
+        }
+        diff_by_index(this, other, other, PositionalDiff)
+    }
+}
+
+impl<T> Apply for PositionalDiff<T>
+where
+    T: Apply,
+{
+    type Parent = Vec<T::Parent>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        apply_by_index(&self.0, source, errs)
+    }
+}
+
+impl<'a, T: Clone + PartialEq + 'a> Diffable<'a> for Box<[T]> {
+    type Diff = AtomicDiff<'a, Self>;
 
     fn diff(&self, other: &'a Self) -> Self::Diff {
-        if self.len() != other.len() {
-            return AtomicDiff::Replaced(&other);
+        if self == other {
+            AtomicDiff::Unchanged
+        } else {
+            AtomicDiff::Replaced(other)
+        }
+    }
+}
+
+/// The element-wise patch of a fixed-size array, keyed by index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ArrayDiff<T, const N: usize>(pub BTreeMap<usize, T>);
+
+impl<'a, T, const N: usize> Diffable<'a> for [T; N]
+where
+    T: Diffable<'a> + Clone + 'a,
+{
+    type Diff = DeepDiff<'a, Self, ArrayDiff<T::Diff, N>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        diff_by_index(self, other, other, ArrayDiff)
+    }
+}
+
+impl<T, const N: usize> Apply for ArrayDiff<T, N>
+where
+    T: Apply,
+{
+    type Parent = [T::Parent; N];
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        apply_by_index(&self.0, source, errs)
+    }
+}
+
+/// The patch of a `VecDeque` used as a ring buffer: `pop_front` elements
+/// are dropped from the front, then `push_back` is appended to the back
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VecDequeDiff<'a, T> {
+    pub pop_front: usize,
+    pub push_back: Vec<&'a T>,
+}
+
+impl<'a, T: Clone + PartialEq + 'a> Diffable<'a> for VecDeque<T> {
+    type Diff = DeepDiff<'a, Self, VecDequeDiff<'a, T>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if self == other {
+            return DeepDiff::Unchanged;
+        }
+        // keep the longest suffix of `self` which is a prefix of `other`
+        match overlap(self, other) {
+            0 => DeepDiff::Replaced(other),
+            kept => DeepDiff::Patched(VecDequeDiff {
+                pop_front: self.len() - kept,
+                push_back: other.range(kept..).collect(),
+            }),
+        }
+    }
+}
+
+/// The length of the longest suffix of `this` which is a prefix of `other`,
+/// by running Knuth-Morris-Pratt over `this` with `other` as the pattern
+fn overlap<T: PartialEq>(this: &VecDeque<T>, other: &VecDeque<T>) -> usize {
+    // fallback[ix] is the length of the longest proper prefix of other[..=ix]
+    // which is also a suffix of it
+    let mut fallback = vec![0; other.len()];
+    let mut len = 0;
+    for ix in 1..other.len() {
+        while len > 0 && other[ix] != other[len] {
+            len = fallback[len - 1];
+        }
+        if other[ix] == other[len] {
+            len += 1;
+        }
+        fallback[ix] = len;
+    }
+    let mut len = 0;
+    for elem in this {
+        while len > 0 && (len == other.len() || other[len] != *elem) {
+            len = fallback[len - 1];
         }
-        for (elem, other_elem) in self.iter().zip(other.iter()) {
-            if elem != other_elem {
-                return AtomicDiff::Replaced(&other);
+        if len < other.len() && other[len] == *elem {
+            len += 1;
+        }
+    }
+    len
+}
+
+impl<'a, T: Clone> Apply for VecDequeDiff<'a, T> {
+    type Parent = VecDeque<T>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        if self.pop_front > source.len() {
+            errs.push(ApplyError::MismatchingBase);
+            return;
+        }
+        source.drain(..self.pop_front);
+        source.extend(self.push_back.iter().map(|&elem| elem.clone()));
+    }
+}
+
+/// The map operations which key-value diffs are built on
+pub(crate) trait KvMap {
+    type Key: Clone;
+    type Value;
+
+    fn kv_iter(&self) -> impl Iterator<Item = (&Self::Key, &Self::Value)>;
+    fn kv_get(&self, k: &Self::Key) -> Option<&Self::Value>;
+    fn kv_get_mut(&mut self, k: &Self::Key) -> Option<&mut Self::Value>;
+    fn kv_insert(&mut self, k: Self::Key, v: Self::Value) -> Option<Self::Value>;
+    fn kv_remove(&mut self, k: &Self::Key) -> Option<Self::Value>;
+}
+
+/// Diff two maps key by key. `whole` is the replacement used when every
+/// value was replaced.
+pub(crate) fn diff_kv<'a, M, Full, Diffs, Patch>(
+    this: &M,
+    other: &'a M,
+    whole: &'a Full,
+    patch: impl FnOnce(Diffs) -> Patch,
+) -> DeepDiff<'a, Full, Patch>
+where
+    M: KvMap,
+    M::Value: Diffable<'a> + 'a,
+    Diffs: Default + Extend<(M::Key, KvDiff<'a, M::Value>)>,
+{
+    let mut diffs = Diffs::default();
+    let mut all_unchanged = true;
+    let mut all_replaced = true;
+    for (k, v) in this.kv_iter() {
+        let Some(other) = other.kv_get(k) else {
+            all_replaced = false;
+            all_unchanged = false;
+            diffs.extend([(k.clone(), KvDiff::Removed)]);
+            continue;
+        };
+        let diff = v.diff(other);
+        if diff.is_unchanged() {
+            // do 'nothing'
+            all_replaced = false;
+        } else {
+            all_replaced &= diff.is_replaced();
+            all_unchanged = false;
+            diffs.extend([(k.clone(), KvDiff::Diff(diff))]);
+        }
+    }
+    for (k, v) in other.kv_iter() {
+        if this.kv_get(k).is_none() {
+            all_unchanged = false;
+            all_replaced = false;
+            diffs.extend([(k.clone(), KvDiff::Inserted(v))]);
+        }
+    }
+    if all_unchanged {
+        DeepDiff::Unchanged
+    } else if all_replaced {
+        DeepDiff::Replaced(whole)
+    } else {
+        DeepDiff::Patched(patch(diffs))
+    }
+}
+
+/// Apply a key-by-key patch to a map
+pub(crate) fn apply_kv<'d, 'a: 'd, M>(
+    diffs: impl IntoIterator<Item = (&'d M::Key, &'d KvDiff<'a, M::Value>)>,
+    source: &mut M,
+    errs: &mut Vec<ApplyError>,
+) where
+    M: KvMap,
+    M::Key: 'd,
+    M::Value: Diffable<'a> + Clone + 'a,
+{
+    for (k, v) in diffs {
+        match v {
+            KvDiff::Removed => {
+                if source.kv_remove(k).is_none() {
+                    errs.push(ApplyError::MissingKey);
+                }
+            }
+            KvDiff::Inserted(val) => {
+                if source.kv_insert(k.clone(), (*val).clone()).is_some() {
+                    errs.push(ApplyError::UnexpectedKey);
+                }
             }
+            KvDiff::Diff(diff) => match source.kv_get_mut(k) {
+                Some(val) => diff.apply_to_base(val, errs),
+                None => errs.push(ApplyError::MissingKey),
+            },
         }
-        AtomicDiff::Unchanged
     }
 }
 
 macro_rules! kv_map_impl {
     ($typ: ident, $bounds: ident) => {
+        impl<K, V> KvMap for $typ<K, V>
+        where
+            K: $bounds + Eq + Clone,
+        {
+            type Key = K;
+            type Value = V;
+
+            fn kv_iter(&self) -> impl Iterator<Item = (&K, &V)> {
+                self.iter()
+            }
+
+            fn kv_get(&self, k: &K) -> Option<&V> {
+                self.get(k)
+            }
+
+            fn kv_get_mut(&mut self, k: &K) -> Option<&mut V> {
+                self.get_mut(k)
+            }
+
+            fn kv_insert(&mut self, k: K, v: V) -> Option<V> {
+                self.insert(k, v)
+            }
+
+            fn kv_remove(&mut self, k: &K) -> Option<V> {
+                self.remove(k)
+            }
+        }
+
         impl<'a, K, V> Diffable<'a> for $typ<K, V>
         where
             K: $bounds + Eq + Clone + 'a,
@@ -278,41 +882,7 @@ macro_rules! kv_map_impl {
             type Diff = DeepDiff<'a, Self, $typ<K, KvDiff<'a, V>>>;
 
             fn diff(&self, other: &'a Self) -> Self::Diff {
-                let mut diffs: $typ<K, KvDiff<V>> = $typ::new();
-                let mut all_unchanged = true;
-                let mut all_replaced = true;
-                for (k, v) in self.iter() {
-                    let Some(other) = other.get(k) else {
-                        all_replaced = false;
-                        all_unchanged = false;
-                        diffs.insert(k.clone(), KvDiff::Removed);
-                        continue;
-                    };
-                    let diff = v.diff(other);
-                    if diff.is_unchanged() {
-                        // do 'nothing'
-                        all_replaced = false;
-                        continue;
-                    } else {
-                        all_replaced &= diff.is_replaced();
-                        all_unchanged = false;
-                        diffs.insert(k.clone(), KvDiff::Diff(diff));
-                    }
-                }
-                for (k, v) in other.iter() {
-                    if !other.contains_key(k) {
-                        all_unchanged = false;
-                        all_replaced = false;
-                        diffs.insert(k.clone(), KvDiff::Inserted(v));
-                    }
-                }
-                if all_unchanged {
-                    DeepDiff::Unchanged
-                } else if all_replaced {
-                    DeepDiff::Replaced(other)
-                } else {
-                    DeepDiff::Patched(diffs)
-                }
+                diff_kv(self, other, other, |diffs| diffs)
             }
         }
 
@@ -324,24 +894,7 @@ macro_rules! kv_map_impl {
             type Parent = $typ<K, V>;
 
             fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
-                for (k, v) in self.into_iter() {
-                    match v {
-                        KvDiff::Removed => match source.remove(&k) {
-                            Some(_) => {}
-                            None => errs.push(ApplyError::MissingKey),
-                        },
-                        KvDiff::Inserted(val) => {
-                            match source.insert((*k).clone(), (*val).clone()) {
-                                Some(_) => errs.push(ApplyError::UnexpectedKey),
-                                None => {}
-                            }
-                        }
-                        KvDiff::Diff(diff) => match source.get_mut(&k) {
-                            Some(val) => diff.apply_to_base(val, errs),
-                            None => errs.push(ApplyError::MissingKey),
-                        },
-                    }
-                }
+                apply_kv(self, source, errs)
             }
         }
     };
@@ -358,6 +911,14 @@ impl<'a> Diffable<'a> for () {
     }
 }
 
+impl<'a, T: 'a> Diffable<'a> for PhantomData<T> {
+    type Diff = Id<Self>;
+
+    fn diff(&self, _: &Self) -> Self::Diff {
+        Id::new()
+    }
+}
+
 impl<'a, T> Diffable<'a> for Box<T>
 where
     T: Diffable<'a>,
@@ -384,6 +945,19 @@ where
     }
 }
 
+impl<'a, T> Replacement<'a> for Box<T>
+where
+    T: Replacement<'a>,
+{
+    fn unchanged() -> Self {
+        Box::new(T::unchanged())
+    }
+
+    fn replaced_by(value: &'a Self::Replaces) -> Self {
+        Box::new(T::replaced_by(value.deref()))
+    }
+}
+
 impl<T> Apply for Box<T>
 where
     T: Apply,
@@ -395,6 +969,131 @@ where
     }
 }
 
+impl<T> ChangedFields for Box<T>
+where
+    T: ChangedFields,
+{
+    type Field = T::Field;
+
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+        self.deref().changed_fields()
+    }
+}
+
+macro_rules! shared_ptr_impl {
+    ($typ: ident, $diff: ident) => {
+        /// The patch of the pointee of a shared pointer, applied copy-on-write
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+        pub struct $diff<T>(pub T);
+
+        impl<'a, T> Diffable<'a> for $typ<T>
+        where
+            T: Diffable<'a> + Clone + 'a,
+        {
+            type Diff = DeepDiff<'a, Self, $diff<T::Diff>>;
+
+            fn diff(&self, other: &'a Self) -> Self::Diff {
+                if $typ::ptr_eq(self, other) {
+                    return DeepDiff::Unchanged;
+                }
+                let diff = self.deref().diff(other.deref());
+                if diff.is_unchanged() {
+                    DeepDiff::Unchanged
+                } else if diff.is_replaced() {
+                    DeepDiff::Replaced(other)
+                } else {
+                    DeepDiff::Patched($diff(diff))
+                }
+            }
+        }
+
+        impl<T> Apply for $diff<T>
+        where
+            T: Apply,
+            T::Parent: Clone,
+        {
+            type Parent = $typ<T::Parent>;
+
+            fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+                self.0.apply_to_base($typ::make_mut(source), errs)
+            }
+        }
+
+        impl<T> ChangedFields for $diff<T>
+        where
+            T: ChangedFields,
+        {
+            type Field = T::Field;
+
+            fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+                self.0.changed_fields()
+            }
+        }
+    };
+}
+
+shared_ptr_impl!(Rc, RcDiff);
+shared_ptr_impl!(Arc, ArcDiff);
+
+/// The patch of a `Cow`, applied copy-on-write
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CowDiff<'b, T>(
+    pub T,
+    #[cfg_attr(feature = "serde", serde(skip))] PhantomData<&'b ()>,
+);
+
+impl<'b, T> CowDiff<'b, T> {
+    pub fn new(diff: T) -> Self {
+        CowDiff(diff, PhantomData)
+    }
+}
+
+impl<'a, 'b: 'a, T> Diffable<'a> for Cow<'b, T>
+where
+    T: Diffable<'a> + Clone + 'a,
+{
+    type Diff = DeepDiff<'a, Self, CowDiff<'b, T::Diff>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        if std::ptr::eq::<T>(self.deref(), other.deref()) {
+            return DeepDiff::Unchanged;
+        }
+        let diff = self.deref().diff(other.deref());
+        if diff.is_unchanged() {
+            DeepDiff::Unchanged
+        } else if diff.is_replaced() {
+            DeepDiff::Replaced(other)
+        } else {
+            DeepDiff::Patched(CowDiff::new(diff))
+        }
+    }
+}
+
+impl<'b, T> Apply for CowDiff<'b, T>
+where
+    T: Apply,
+    T::Parent: Clone + 'b,
+{
+    type Parent = Cow<'b, T::Parent>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        self.0.apply_to_base(source.to_mut(), errs)
+    }
+}
+
+impl<'b, T> ChangedFields for CowDiff<'b, T>
+where
+    T: ChangedFields,
+{
+    type Field = T::Field;
+
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+        self.0.changed_fields()
+    }
+}
+
 impl<'a, T> Diffable<'a> for Option<T>
 where
     T: Diffable<'a> + Clone + 'a,
@@ -433,6 +1132,83 @@ where
     }
 }
 
+impl<T> ChangedFields for Option<T>
+where
+    T: ChangedFields,
+{
+    type Field = T::Field;
+
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+        self.iter().flat_map(|diff| diff.changed_fields())
+    }
+}
+
+impl<'a, T, E> Diffable<'a> for Result<T, E>
+where
+    T: Diffable<'a> + Clone + 'a,
+    E: Diffable<'a> + Clone + 'a,
+{
+    type Diff = DeepDiff<'a, Self, Result<T::Diff, E::Diff>>;
+
+    fn diff(&self, other: &'a Self) -> Self::Diff {
+        match (self, other) {
+            (Ok(_), Err(_)) | (Err(_), Ok(_)) => DeepDiff::Replaced(other),
+            (Ok(l), Ok(r)) => {
+                let diff = l.diff(r);
+                if diff.is_unchanged() {
+                    DeepDiff::Unchanged
+                } else if diff.is_replaced() {
+                    DeepDiff::Replaced(other)
+                } else {
+                    DeepDiff::Patched(Ok(diff))
+                }
+            }
+            (Err(l), Err(r)) => {
+                let diff = l.diff(r);
+                if diff.is_unchanged() {
+                    DeepDiff::Unchanged
+                } else if diff.is_replaced() {
+                    DeepDiff::Replaced(other)
+                } else {
+                    DeepDiff::Patched(Err(diff))
+                }
+            }
+        }
+    }
+}
+
+impl<T, E> Apply for Result<T, E>
+where
+    T: Apply,
+    E: Apply,
+{
+    type Parent = Result<T::Parent, E::Parent>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        match (self, source) {
+            (Ok(diff), Ok(src)) => diff.apply_to_base(src, errs),
+            (Err(diff), Err(src)) => diff.apply_to_base(src, errs),
+            _ => errs.push(ApplyError::MismatchingEnum),
+        }
+    }
+}
+
+impl<T, E> ChangedFields for Result<T, E>
+where
+    T: ChangedFields,
+    E: ChangedFields,
+{
+    type Field = Result<T::Field, E::Field>;
+
+    fn changed_fields(&self) -> impl Iterator<Item = Self::Field> + '_ {
+        let (ok, err) = match self {
+            Ok(diff) => (Some(diff.changed_fields().map(Ok)), None),
+            Err(diff) => (None, Some(diff.changed_fields().map(Err))),
+        };
+        ok.into_iter().flatten().chain(err.into_iter().flatten())
+    }
+}
+
 macro_rules! tuple_impl {
     ( $( $tup:ident $ix:tt ),* ) => {
         impl<'a, $( $tup ),*> Diffable<'a> for ( $( $tup, )* )
@@ -471,6 +1247,19 @@ macro_rules! tuple_impl {
             }
         }
 
+        impl<'a, $( $tup ),*> Replacement<'a> for ( $( $tup, )* )
+        where
+            $( $tup: Replacement<'a> ),*
+        {
+            fn unchanged() -> Self {
+                ( $( $tup::unchanged(), )* )
+            }
+
+            fn replaced_by(value: &'a Self::Replaces) -> Self {
+                ( $( $tup::replaced_by(&value.$ix), )* )
+            }
+        }
+
         impl< $( $tup ),*> Apply for ( $( $tup, )*)
         where
             $( $tup: Apply ),*
@@ -602,6 +1391,7 @@ mod tests {
 
     impl<'a> Diffable<'a> for Child2 {
         type Diff = DeepDiff<'a, Self, Child2Diff<'a>>;
+        #[allow(clippy::unit_arg)]
         fn diff(&self, other: &'a Self) -> Self::Diff {
             let a = self.a.diff(&other.a);
             let b = self.b.diff(&other.b);
@@ -733,7 +1523,7 @@ mod tests {
             let diff = p3.diff(&p4);
             let expect = DeepDiff::Patched(ParentDiff {
                 c1: DeepDiff::Unchanged,
-                c2: AtomicDiff::Unchanged,
+                c2: DeepDiff::Unchanged,
                 c3: DeepDiff::Unchanged,
                 val: AtomicDiff::Replaced(&mello),
             });
@@ -746,7 +1536,7 @@ mod tests {
             let dummy = dummy_child2();
             let bad_patch = DeepDiff::Patched(ParentDiff {
                 c1: DeepDiff::Unchanged,
-                c2: AtomicDiff::Unchanged,
+                c2: DeepDiff::Unchanged,
                 c3: DeepDiff::Patched(
                     [
                         (543, KvDiff::Removed),          // key does not exist
@@ -0,0 +1,99 @@
+//! Fast diffs for large buffers of plain-old-data.
+//!
+//! The generic `Vec` impl compares element by element. For `bytemuck::Pod`
+//! elements the buffers can instead be compared as raw bytes, a chunk at a
+//! time, and the diff records just the ranges which changed. Use it as a field
+//! attribute:
+//!
+//! ```
+//! use difficient::Diffable;
+//!
+//! #[derive(Diffable, PartialEq, Debug, Clone)]
+//! struct Simulation {
+//!     #[diffable(with = "difficient::pod::Chunked<Vec<f32>>")]
+//!     positions: Vec<f32>,
+//! }
+//! ```
+//!
+//! Elements are compared by their bytes, so `0.0` and `-0.0` differ while
+//! identical `NaN`s do not.
+
+use std::{marker::PhantomData, mem::size_of, ops::Range};
+
+use bytemuck::Pod;
+
+use crate::{Apply, ApplyError, DeepDiff, DiffableRemote};
+
+/// The number of bytes compared at a time
+const CHUNK_BYTES: usize = 4096;
+
+/// Diff a `Vec` of `Pod` elements by comparing its bytes in chunks
+pub struct Chunked<V>(PhantomData<V>);
+
+/// The ranges of a buffer which changed, with their new contents
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChangedRanges<'a, T>(pub Vec<(Range<usize>, &'a [T])>);
+
+impl<'a, T> DiffableRemote<'a> for Chunked<Vec<T>>
+where
+    T: Pod,
+{
+    type Remote = Vec<T>;
+    type Diff = DeepDiff<'a, Vec<T>, ChangedRanges<'a, T>>;
+
+    fn diff(this: &Vec<T>, other: &'a Vec<T>) -> Self::Diff {
+        if this.len() != other.len() {
+            return DeepDiff::Replaced(other);
+        }
+        let chunk = (CHUNK_BYTES / size_of::<T>().max(1)).max(1);
+        let differs = |range: Range<usize>| {
+            bytemuck::cast_slice::<T, u8>(&this[range.clone()])
+                != bytemuck::cast_slice::<T, u8>(&other[range])
+        };
+        let mut ranges = Vec::new();
+        let mut changed = 0;
+        let mut start = 0;
+        while start < this.len() {
+            let end = (start + chunk).min(this.len());
+            if !differs(start..end) {
+                start = end;
+                continue;
+            }
+            // extend over the following changed chunks, then trim the
+            // unchanged elements at either end
+            let mut run_end = end;
+            while run_end < this.len() && differs(run_end..(run_end + chunk).min(this.len())) {
+                run_end = (run_end + chunk).min(this.len());
+            }
+            let first = (start..run_end).find(|&ix| differs(ix..ix + 1)).unwrap();
+            let last = (first..run_end)
+                .rev()
+                .find(|&ix| differs(ix..ix + 1))
+                .unwrap();
+            changed += last + 1 - first;
+            ranges.push((first..last + 1, &other[first..last + 1]));
+            start = run_end;
+        }
+        if ranges.is_empty() {
+            DeepDiff::Unchanged
+        } else if changed == this.len() {
+            DeepDiff::Replaced(other)
+        } else {
+            DeepDiff::Patched(ChangedRanges(ranges))
+        }
+    }
+}
+
+impl<T: Pod> Apply for ChangedRanges<'_, T> {
+    type Parent = Vec<T>;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        for (range, values) in &self.0 {
+            match source.get_mut(range.clone()) {
+                Some(target) if target.len() == values.len() => target.copy_from_slice(values),
+                _ => errs.push(ApplyError::MismatchingBase),
+            }
+        }
+    }
+}
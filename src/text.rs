@@ -0,0 +1,160 @@
+//! Span-based diffs for `String`s.
+//!
+//! A plain `String` is diffed atomically, so a one-character edit clones the
+//! whole string. The types here diff text into insert/delete spans instead, at
+//! line, word or grapheme granularity. Use them as a field attribute:
+//!
+//! ```
+//! use difficient::{Diffable, DeepDiff};
+//!
+//! #[derive(Diffable, PartialEq, Debug, Clone)]
+//! struct Note {
+//!     #[diffable(with = "difficient::text::Lines")]
+//!     body: String,
+//! }
+//!
+//! let mut first = Note { body: "one\ntwo\nthree\n".into() };
+//! let second = Note { body: "one\n2\nthree\n".into() };
+//! let diff = first.diff(&second);
+//! first.apply(diff).unwrap();
+//! assert_eq!(first, second);
+//! ```
+
+use crate::{Apply, ApplyError, DeepDiff, DiffableRemote};
+
+/// A single edit to the base text. `Keep` and `Delete` count bytes of the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TextEdit<'a> {
+    Keep(usize),
+    Delete(usize),
+    Insert(&'a str),
+}
+
+/// The edits which turn the base text into the new text, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TextDiff<'a>(pub Vec<TextEdit<'a>>);
+
+impl Apply for TextDiff<'_> {
+    type Parent = String;
+
+    fn apply_to_base(&self, source: &mut Self::Parent, errs: &mut Vec<ApplyError>) {
+        let mut out = String::with_capacity(source.len());
+        let mut cursor: usize = 0;
+        for edit in &self.0 {
+            match *edit {
+                TextEdit::Keep(len) | TextEdit::Delete(len) => {
+                    let Some(span) = source.get(cursor..cursor.saturating_add(len)) else {
+                        errs.push(ApplyError::MismatchingBase);
+                        return;
+                    };
+                    if let TextEdit::Keep(_) = edit {
+                        out.push_str(span);
+                    }
+                    cursor += len;
+                }
+                TextEdit::Insert(text) => out.push_str(text),
+            }
+        }
+        if cursor != source.len() {
+            errs.push(ApplyError::MismatchingBase);
+            return;
+        }
+        *source = out;
+    }
+}
+
+impl<'a> DeepDiff<'a, String, TextDiff<'a>> {
+    /// Render the diff of `base` as a unified diff, with three lines of context
+    pub fn unified_diff(&self, base: &str) -> Result<String, Vec<ApplyError>> {
+        let new = match self {
+            DeepDiff::Unchanged => return Ok(String::new()),
+            DeepDiff::Replaced(new) => (*new).clone(),
+            DeepDiff::Patched(patch) => {
+                let mut new = base.to_string();
+                let mut errs = Vec::new();
+                patch.apply_to_base(&mut new, &mut errs);
+                if !errs.is_empty() {
+                    return Err(errs);
+                }
+                new
+            }
+        };
+        Ok(similar::TextDiff::from_lines(base, &new)
+            .unified_diff()
+            .to_string())
+    }
+}
+
+fn text_diff<'a>(
+    diff: similar::TextDiff<'_, '_, '_, str>,
+    other: &'a String,
+) -> DeepDiff<'a, String, TextDiff<'a>> {
+    let old_len = |range: std::ops::Range<usize>| -> usize {
+        diff.old_slices()[range].iter().map(|s| s.len()).sum()
+    };
+    let mut edits = Vec::new();
+    let mut kept = false;
+    let mut new_pos = 0;
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let new_len: usize = diff.new_slices()[new_range].iter().map(|s| s.len()).sum();
+        let inserted = &other[new_pos..new_pos + new_len];
+        new_pos += new_len;
+        match tag {
+            similar::DiffTag::Equal => {
+                kept = true;
+                edits.push(TextEdit::Keep(new_len));
+            }
+            similar::DiffTag::Delete => edits.push(TextEdit::Delete(old_len(old_range))),
+            similar::DiffTag::Insert => edits.push(TextEdit::Insert(inserted)),
+            similar::DiffTag::Replace => {
+                edits.push(TextEdit::Delete(old_len(old_range)));
+                edits.push(TextEdit::Insert(inserted));
+            }
+        }
+    }
+    if edits.iter().all(|edit| matches!(edit, TextEdit::Keep(_))) {
+        DeepDiff::Unchanged
+    } else if !kept {
+        DeepDiff::Replaced(other)
+    } else {
+        DeepDiff::Patched(TextDiff(edits))
+    }
+}
+
+macro_rules! text_granularity {
+    ($(#[$doc: meta])* $name: ident, $method: ident) => {
+        $(#[$doc])*
+        pub struct $name;
+
+        impl<'a> DiffableRemote<'a> for $name {
+            type Remote = String;
+            type Diff = DeepDiff<'a, String, TextDiff<'a>>;
+
+            fn diff(this: &String, other: &'a String) -> Self::Diff {
+                if this == other {
+                    return DeepDiff::Unchanged;
+                }
+                text_diff(similar::TextDiffConfig::default().$method(this, other), other)
+            }
+        }
+    };
+}
+
+text_granularity!(
+    /// Diff text line by line
+    Lines,
+    diff_lines
+);
+text_granularity!(
+    /// Diff text by unicode word boundaries
+    Words,
+    diff_unicode_words
+);
+text_granularity!(
+    /// Diff text by extended grapheme clusters
+    Graphemes,
+    diff_graphemes
+);
@@ -0,0 +1,92 @@
+//! Approximate comparison of floats.
+//!
+//! Derived diffs use this for fields with the `epsilon`, `relative` or `ulps`
+//! attributes, so that float noise doesn't show up as a change:
+//!
+//! ```
+//! use difficient::Diffable;
+//!
+//! #[derive(Diffable, PartialEq, Debug, Clone)]
+//! struct Body {
+//!     #[diffable(epsilon = 1e-6)]
+//!     x: f64,
+//! }
+//! ```
+
+use crate::{Diffable, Replacement};
+
+/// How far apart two floats may be and still count as unchanged.
+///
+/// Values are unchanged if they are within any of the tolerances. A zero
+/// tolerance is no tolerance at all, so the default only treats equal values
+/// (and `NaN` with `NaN`) as unchanged, and an infinity is only ever within
+/// tolerance of itself. The derive builds one of these from the `epsilon`,
+/// `relative` and `ulps` field attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tolerance {
+    /// The largest absolute difference
+    pub absolute: f64,
+    /// The largest difference, as a fraction of the larger magnitude
+    pub relative: f64,
+    /// The largest number of representable values between the two
+    pub ulps: u64,
+}
+
+impl Tolerance {
+    /// Diff `this` against `other`, reporting `Unchanged` if they are within tolerance
+    pub fn diff<'a, T>(&self, this: &T, other: &'a T) -> T::Diff
+    where
+        T: ApproxEq + Diffable<'a>,
+        T::Diff: Replacement<'a>,
+    {
+        if this.approx_eq(other, self) {
+            T::Diff::unchanged()
+        } else {
+            this.diff(other)
+        }
+    }
+}
+
+/// Equality within a `Tolerance`
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, tolerance: &Tolerance) -> bool;
+}
+
+macro_rules! approx_eq_float {
+    ($($typ: ty, $bits: ty;)*) => ($(
+        impl ApproxEq for $typ {
+            fn approx_eq(&self, other: &Self, tolerance: &Tolerance) -> bool {
+                let (a, b) = (*self, *other);
+                if a == b || (a.is_nan() && b.is_nan()) {
+                    return true;
+                }
+                // an infinity is only equal to itself, which the relative
+                // tolerance would otherwise allow for any value
+                if a.is_nan() || b.is_nan() || a.is_infinite() || b.is_infinite() {
+                    return false;
+                }
+                let diff = (a - b).abs() as f64;
+                if diff <= tolerance.absolute
+                    || diff <= tolerance.relative * a.abs().max(b.abs()) as f64
+                {
+                    return true;
+                }
+                // map the bits onto a line which is ordered like the floats
+                let ordered = |x: $typ| {
+                    let bits = x.to_bits() as $bits;
+                    if bits < 0 {
+                        <$bits>::MIN - bits
+                    } else {
+                        bits
+                    }
+                };
+                (ordered(a) as i128 - ordered(b) as i128).unsigned_abs() <= tolerance.ulps as u128
+            }
+        }
+    )*);
+}
+
+approx_eq_float! {
+    f32, i32;
+    f64, i64;
+}
@@ -7,7 +7,7 @@ impl<'a> Diffable<'a> for uuid::Uuid {
         if self == other {
             AtomicDiff::Unchanged
         } else {
-            AtomicDiff::Replaced(&other)
+            AtomicDiff::Replaced(other)
         }
     }
 }
@@ -44,6 +44,7 @@ struct SimpleStruct {
 }
 
 #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[allow(clippy::type_complexity)]
 struct StrangeStruct {
     r#try: Option<Box<(u32, (&'static str, Box<u64>))>>,
 }
@@ -61,8 +62,450 @@ enum SimpleEnum {
     Third { x: String, y: () },
 }
 
+#[derive(difficient::Diffable, PartialEq, Eq, Hash, Debug, Clone)]
+#[diffable(diff_derive(Eq, Hash), diff_attr(allow(clippy::enum_variant_names)))]
+enum HashableEnum {
+    AOne(i32),
+    ATwo { x: Option<u64> },
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", diffable(diff_derive(serde::Serialize)))]
+struct SerializableStruct {
+    x: String,
+    y: (i32, bool),
+}
+
+mod prelude {
+    pub use difficient;
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(diff_name = "RenamedPatch", crate = "crate::prelude::difficient")]
+struct Renamed {
+    x: i32,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct User {
+    name: String,
+    #[diffable(nested)]
+    address: Address,
+    #[diffable(nested)]
+    previous_address: Option<Box<Address>>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Address {
+    street: String,
+    city: String,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(mask)]
+struct Entity {
+    position: (i32, i32),
+    health: u8,
+    name: String,
+    stats: HashMap<String, u32>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(
+    partial,
+    partial_derive(serde::Serialize, serde::Deserialize),
+    partial_field_attr(serde(skip_serializing_if = "Option::is_none"))
+)]
+struct Profile {
+    name: String,
+    age: u32,
+    tags: Vec<String>,
+    location: Option<(f64, f64)>,
+}
+
+// stands in for a type from a crate we don't own
+mod vendor {
+    #[derive(PartialEq, Debug, Clone)]
+    pub struct Config {
+        pub name: String,
+        pub retries: u32,
+        pub mode: Mode,
+    }
+
+    #[derive(PartialEq, Debug, Clone)]
+    pub enum Mode {
+        Fast,
+        Careful { timeout: u64 },
+    }
+}
+
+#[derive(difficient::Diffable)]
+#[diffable(remote = "vendor::Config")]
+struct ConfigDef {
+    name: String,
+    retries: u32,
+    #[diffable(with = "ModeDef")]
+    mode: vendor::Mode,
+}
+
+#[derive(difficient::Diffable)]
+#[diffable(remote = "vendor::Mode")]
+enum ModeDef {
+    Fast,
+    Careful { timeout: u64 },
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Service {
+    port: u16,
+    #[diffable(with = "ConfigDef")]
+    config: vendor::Config,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Permissions {
+    #[diffable(via = "Flags")]
+    bits: u32,
+}
+
+// semantically, the bits are a set of flags
+#[derive(PartialEq, Debug, Clone)]
+struct Flags(u32);
+
+// owns the flags which flipped, so it doesn't borrow from the proxies
+#[derive(PartialEq, Debug, Clone)]
+struct FlagsDiff {
+    set: u32,
+    cleared: u32,
+}
+
+impl From<&u32> for Flags {
+    fn from(bits: &u32) -> Self {
+        Flags(*bits)
+    }
+}
+
+impl From<Flags> for u32 {
+    fn from(flags: Flags) -> Self {
+        flags.0
+    }
+}
+
+impl difficient::Diffable<'_> for Flags {
+    type Diff = FlagsDiff;
+
+    fn diff(&self, other: &Self) -> Self::Diff {
+        FlagsDiff {
+            set: other.0 & !self.0,
+            cleared: self.0 & !other.0,
+        }
+    }
+}
+
+impl difficient::Replace for FlagsDiff {
+    type Replaces = Flags;
+
+    fn is_unchanged(&self) -> bool {
+        self.set == 0 && self.cleared == 0
+    }
+
+    fn is_replaced(&self) -> bool {
+        false
+    }
+}
+
+impl difficient::Apply for FlagsDiff {
+    type Parent = Flags;
+
+    fn apply_to_base(&self, source: &mut Flags, _: &mut Vec<difficient::ApplyError>) {
+        source.0 = (source.0 | self.set) & !self.cleared;
+    }
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(transition)]
+enum Job {
+    Queued,
+    Running {
+        id: u64,
+        progress: Vec<f32>,
+    },
+    Paused {
+        id: u64,
+        progress: Vec<f32>,
+        reason: String,
+    },
+    Failed(String),
+}
+
+// no PartialEq needed
+#[derive(difficient::Diffable, Debug, Clone)]
+enum Color {
+    Red,
+    Green,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(atomic)]
+enum Shape {
+    Circle { radius: f64 },
+    Square(f64),
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[repr(u8)]
+enum Discriminants {
+    A = 1,
+    B = 5,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[repr(u8)]
+enum ReprWithFields {
+    A(u8) = 1,
+    B { x: i32 } = 3,
+    C = 10,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+enum NonExhaustive {
+    A,
+    #[non_exhaustive]
+    B {
+        x: i32,
+    },
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+struct NonExhaustiveStruct {
+    x: i32,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+enum SingleVariant {
+    Only(i32),
+}
+
+// a #[non_exhaustive] enum from another crate, so the mirror may leave out variants
+#[derive(difficient::Diffable)]
+#[diffable(remote = "std::io::ErrorKind")]
+#[non_exhaustive]
+enum ErrorKindDef {
+    NotFound,
+    PermissionDenied,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(eq_first)]
+struct Tick {
+    positions: HashMap<u32, (i32, i32)>,
+    names: HashMap<u32, String>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(eq_first)]
+enum Cell {
+    Empty,
+    Full { tick: Tick },
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct World {
+    #[diffable(nested)]
+    terrain: std::sync::Arc<Address>,
+    #[diffable(nested)]
+    local: std::rc::Rc<Address>,
+    label: std::borrow::Cow<'static, (u32, String)>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Stats {
+    count: usize,
+    total: u128,
+    initial: char,
+    id: std::num::NonZeroU32,
+    counter: std::num::Wrapping<u8>,
+    elapsed: std::time::Duration,
+    path: std::path::PathBuf,
+    host: std::net::SocketAddr,
+    order: std::cmp::Ordering,
+    window: std::ops::Range<i64>,
+    rank: std::cmp::Reverse<u32>,
+    label: &'static str,
+    marker: std::marker::PhantomData<u64>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Telemetry {
+    position: [f32; 3],
+    samples: std::collections::VecDeque<u32>,
+    tags: Box<[String]>,
+    events: std::collections::LinkedList<u8>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Cached {
+    response: Result<Address, (u16, String)>,
+}
+
+#[cfg(feature = "text")]
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Note {
+    #[diffable(with = "difficient::text::Lines")]
+    body: String,
+    #[diffable(with = "difficient::text::Words")]
+    title: String,
+    #[diffable(with = "difficient::text::Graphemes")]
+    emoji: String,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Attachment {
+    #[diffable(with = "difficient::delta::Delta")]
+    data: Vec<u8>,
+    #[diffable(with = "difficient::delta::Delta<Box<[u8]>>")]
+    thumbnail: Box<[u8]>,
+}
+
+#[cfg(feature = "bytes")]
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Frame {
+    raw: bytes::Bytes,
+    #[diffable(with = "difficient::delta::Delta<bytes::Bytes>")]
+    payload: bytes::Bytes,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Metrics {
+    hits: difficient::counter::Counter<u8>,
+    balance: difficient::counter::Counter<i64>,
+    sequence: difficient::counter::Counter<std::num::Wrapping<u8>>,
+    load: difficient::counter::Counter<f64>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(mask, eq_first)]
+struct Body {
+    #[diffable(epsilon = 1e-6)]
+    x: f64,
+    #[diffable(relative = 1e-3)]
+    mass: f32,
+    #[diffable(ulps = 4)]
+    spin: f64,
+    #[diffable(epsilon = 1e-6, ulps = 4)]
+    charge: f64,
+    temperature: f64,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Table {
+    #[diffable(with = "difficient::Positional<Vec<Address>>")]
+    rows: Vec<Address>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct EventLog {
+    events: Vec<String>,
+}
+
+#[cfg(feature = "bytemuck")]
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Simulation {
+    #[diffable(with = "difficient::pod::Chunked<Vec<f32>>")]
+    positions: Vec<f32>,
+    #[diffable(with = "difficient::pod::Chunked<Vec<[u8; 4]>>")]
+    pixels: Vec<[u8; 4]>,
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Settings {
+    name: String,
+    extra: serde_json::Value,
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+struct Legacy {
+    id: u32,
+    owner: Option<String>,
+    scores: std::collections::BTreeMap<String, Vec<u8>>,
+    mode: LegacyMode,
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+enum LegacyMode {
+    Off,
+    On { level: u8 },
+}
+
+#[cfg(feature = "indexmap")]
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+struct Grid {
+    columns: indexmap::IndexMap<String, u32>,
+    hidden: indexmap::IndexSet<String>,
+}
+
+#[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+#[diffable(eq_first)]
+enum Reading {
+    Empty,
+    Value(#[diffable(epsilon = 0.1)] f64),
+    Pair {
+        #[diffable(ulps = 2)]
+        a: f32,
+        b: i32,
+    },
+}
+
 mod just_check_they_compile {
 
+    // field names which would collide as field enum variants
+    #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+    #[diffable(fields = false)]
+    #[allow(non_snake_case)]
+    struct CamelCollision {
+        foo_bar: i32,
+        fooBar: i32,
+        x: i32,
+        _x: i32,
+    }
+
+    // shared transition fields need not implement `Default`
+    #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+    #[diffable(transition)]
+    #[allow(dead_code)]
+    enum Lease {
+        Held {
+            owner: std::num::NonZeroU32,
+        },
+        Released {
+            owner: std::num::NonZeroU32,
+            at: u64,
+        },
+    }
+
+    // a user type with the name the field enum would have
+    #[allow(dead_code)]
+    enum SimpleField {}
+
+    #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+    #[diffable(fields = false)]
+    struct Simple {
+        x: i32,
+    }
+
+    // partials only derive serde when asked to, so their fields need not support it
+    #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
+    #[diffable(partial)]
+    struct PartialWithoutSerde {
+        inner: Simple,
+    }
+
     #[derive(difficient::Diffable, PartialEq, Debug, Clone)]
     #[allow(dead_code)]
     enum FieldlessEnum {
@@ -81,6 +524,7 @@ mod just_check_they_compile {
     #[allow(non_camel_case_types)]
     #[allow(non_snake_case)]
     #[allow(dead_code)]
+    #[allow(clippy::upper_case_acronyms)]
     enum dumb_Enum_noWarnings {
         lowercase { UPPERCASE: i32 },
         UPPERCASE { lowercase: i32 },
@@ -179,4 +623,1442 @@ mod tests {
             assert_eq!(it3, it4);
         }
     }
+
+    #[test]
+    fn test_diff_derive() {
+        use std::collections::HashSet;
+
+        let it1 = HashableEnum::AOne(1);
+        let it2 = HashableEnum::AOne(2);
+        let it3 = HashableEnum::ATwo { x: Some(3) };
+        let it4 = HashableEnum::ATwo { x: Some(4) };
+
+        let diffs: HashSet<_> = [
+            it1.diff(&it2),
+            it1.diff(&it2),
+            it3.diff(&it4),
+            it4.diff(&it4),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs.contains(&difficient::DeepDiff::Unchanged));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_diff() {
+        let it1 = SerializableStruct {
+            x: "hello".into(),
+            y: (1, true),
+        };
+        let it2 = SerializableStruct {
+            x: "hello".into(),
+            y: (2, true),
+        };
+        let diff = it1.diff(&it2);
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Patched":{"x":"Unchanged","y":[{"Replaced":2},"Unchanged"]}}"#
+        );
+    }
+
+    #[test]
+    fn test_diff_name_and_crate() {
+        let mut it1 = Renamed { x: 1 };
+        let it2 = Renamed { x: 2 };
+        let diff = it1.diff(&it2);
+        assert_eq!(diff, difficient::DeepDiff::Replaced(&it2));
+        let patch = RenamedPatch {
+            x: difficient::AtomicDiff::Replaced(&3),
+        };
+        it1.apply(difficient::DeepDiff::Patched(patch)).unwrap();
+        assert_eq!(it1, Renamed { x: 3 });
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        use difficient::ChangedFields;
+
+        let address = |street: &str, city: &str| Address {
+            street: street.into(),
+            city: city.into(),
+        };
+        let it1 = User {
+            name: "bob".into(),
+            address: address("high street", "london"),
+            previous_address: Some(Box::new(address("main street", "paris"))),
+        };
+        let it2 = User {
+            name: "bob".into(),
+            address: address("high street", "leeds"),
+            previous_address: None,
+        };
+        let it3 = User {
+            name: "rob".into(),
+            address: address("low street", "york"),
+            previous_address: Some(Box::new(address("main street", "rome"))),
+        };
+
+        let difficient::DeepDiff::Patched(diff) = it1.diff(&it2) else {
+            panic!("expected patch")
+        };
+        let changed: Vec<_> = diff.changed_fields().collect();
+        assert_eq!(
+            changed,
+            [
+                UserField::Address(Some(AddressField::City)),
+                UserField::PreviousAddress(None),
+            ]
+        );
+
+        let difficient::DeepDiff::Patched(diff) = it1.diff(&it3) else {
+            panic!("expected patch")
+        };
+        let changed: Vec<_> = diff.changed_fields().collect();
+        assert_eq!(
+            changed,
+            [
+                UserField::Name,
+                UserField::Address(None),
+                UserField::PreviousAddress(Some(Some(AddressField::City))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_mask() {
+        let it1 = Entity {
+            position: (0, 0),
+            health: 100,
+            name: "orc".into(),
+            stats: [("strength".to_string(), 10)].into_iter().collect(),
+        };
+        let mut it2 = it1.clone();
+        assert!(it1.changed_mask(&it2).is_empty());
+
+        // maps are compared, not diffed
+        it2.stats.insert("speed".into(), 3);
+        assert_eq!(it1.changed_mask(&it2), EntityMask::STATS);
+        it2.stats = it1.stats.clone();
+
+        it2.position = (1, 0);
+        it2.name = "troll".into();
+        let mask = it1.changed_mask(&it2);
+        assert_eq!(mask, EntityMask::POSITION | EntityMask::NAME);
+        assert!(mask.contains(EntityMask::NAME));
+        assert!(!mask.intersects(EntityMask::HEALTH));
+        assert_eq!(EntityMask::all().bits(), 0b1111);
+    }
+
+    #[test]
+    fn test_partial() {
+        use difficient::Apply;
+
+        let mut it1 = Profile {
+            name: "alice".into(),
+            age: 30,
+            tags: vec!["admin".into()],
+            location: None,
+        };
+
+        let partial = ProfilePartial {
+            age: Some(31),
+            location: Some(Some((51.5, -0.1))),
+            ..Default::default()
+        };
+        it1.apply(partial.to_diff()).unwrap();
+        assert_eq!(it1.age, 31);
+        assert_eq!(it1.location, Some((51.5, -0.1)));
+        assert_eq!(it1.name, "alice");
+
+        // an owned partial applies directly
+        let partial = ProfilePartial {
+            name: Some("bob".into()),
+            ..Default::default()
+        };
+        let mut errs = Vec::new();
+        partial.apply_to_base(&mut it1, &mut errs);
+        assert!(errs.is_empty());
+        assert_eq!(it1.name, "bob");
+        assert_eq!(it1.age, 31);
+
+        let empty = ProfilePartial::default();
+        assert_eq!(empty.to_diff(), difficient::DeepDiff::Unchanged);
+    }
+
+    #[test]
+    fn test_partial_serde() {
+        let partial: ProfilePartial =
+            serde_json::from_str(r#"{"age": 31, "location": [51.5, -0.1]}"#).unwrap();
+        assert_eq!(partial.name, None);
+        assert_eq!(partial.age, Some(31));
+
+        let json = serde_json::to_string(&partial).unwrap();
+        assert_eq!(json, r#"{"age":31,"location":[51.5,-0.1]}"#);
+    }
+
+    #[test]
+    fn test_remote() {
+        use difficient::{DeepDiff, DiffableRemote};
+
+        let mut it1 = Service {
+            port: 80,
+            config: vendor::Config {
+                name: "web".into(),
+                retries: 3,
+                mode: vendor::Mode::Careful { timeout: 10 },
+            },
+        };
+        let mut it2 = it1.clone();
+        it2.config.mode = vendor::Mode::Careful { timeout: 20 };
+
+        let diff = it1.diff(&it2);
+        let DeepDiff::Patched(ServiceDiff { config, .. }) = &diff else {
+            panic!("expected patch")
+        };
+        assert!(matches!(config, DeepDiff::Patched(ConfigDefDiff { .. })));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        let mut config = it1.config.clone();
+        let diff = ConfigDef::diff(&config, &it2.config);
+        assert_eq!(diff, DeepDiff::Unchanged);
+
+        let other = vendor::Config {
+            name: "api".into(),
+            retries: 3,
+            mode: vendor::Mode::Fast,
+        };
+        let diff = ConfigDef::diff(&config, &other);
+        ConfigDef::apply(&mut config, diff).unwrap();
+        assert_eq!(config, other);
+    }
+
+    #[test]
+    fn test_via() {
+        let it1 = Permissions { bits: 0b0011 };
+        let it2 = Permissions { bits: 0b0110 };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        // the patch is the proxy's own diff
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(PermissionsDiff { bits }) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(
+            bits,
+            &difficient::ViaDiff::Patched(FlagsDiff {
+                set: 0b0100,
+                cleared: 0b0001,
+            })
+        );
+
+        // flags which the diff doesn't touch are kept
+        let mut it3 = Permissions { bits: 0b1011 };
+        it3.apply(diff).unwrap();
+        assert_eq!(it3, Permissions { bits: 0b1110 });
+    }
+
+    #[test]
+    fn test_transition() {
+        use difficient::{AtomicDiff, DeepDiff};
+
+        let mut it1 = Job::Running {
+            id: 7,
+            progress: vec![0.5],
+        };
+        let it2 = Job::Paused {
+            id: 7,
+            progress: vec![0.6],
+            reason: "lunch".into(),
+        };
+        let reason = "lunch".to_string();
+        let progress = vec![0.6];
+
+        let diff = it1.diff(&it2);
+        let expect = DeepDiff::Patched(JobDiff::VariantChanged {
+            to: JobDiffVariant::Paused,
+            shared: JobDiffShared {
+                id: Some(AtomicDiff::Unchanged),
+                progress: Some(DeepDiff::Replaced(&progress)),
+            },
+            new: JobDiffNew {
+                reason: Some(&reason),
+                ..Default::default()
+            },
+        });
+        assert_eq!(diff, expect);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // no fields shared
+        let it3 = Job::Failed("oops".into());
+        assert_eq!(it1.diff(&it3), DeepDiff::Replaced(&it3));
+        assert_eq!(Job::Queued.diff(&it2), DeepDiff::Replaced(&it2));
+
+        // shared fields are reused from the base being patched
+        let mut it4 = Job::Running {
+            id: 8,
+            progress: vec![0.5],
+        };
+        let diff = Job::Running {
+            id: 7,
+            progress: vec![0.5],
+        }
+        .diff(&it2);
+        it4.apply(diff.clone()).unwrap();
+        assert_eq!(
+            it4,
+            Job::Paused {
+                id: 8,
+                progress: vec![0.6],
+                reason: "lunch".into()
+            }
+        );
+
+        let mut it5 = Job::Queued;
+        let err = it5.apply(diff).unwrap_err();
+        assert_eq!(err, [difficient::ApplyError::MismatchingEnum]);
+        assert_eq!(it5, Job::Queued);
+    }
+
+    #[test]
+    fn test_atomic_enums() {
+        use difficient::AtomicDiff;
+
+        let mut it1 = Color::Red;
+        let it2 = Color::Green;
+        assert!(matches!(it1.diff(&it1.clone()), AtomicDiff::Unchanged));
+        let diff = it1.diff(&it2);
+        assert!(matches!(diff, AtomicDiff::Replaced(Color::Green)));
+        it1.apply(diff).unwrap();
+        assert!(matches!(it1, Color::Green));
+
+        let mut it3 = Shape::Circle { radius: 1.0 };
+        let it4 = Shape::Circle { radius: 2.0 };
+        let it5 = Shape::Square(2.0);
+        assert_eq!(it3.diff(&it3.clone()), AtomicDiff::Unchanged);
+        assert_eq!(it3.diff(&it4), AtomicDiff::Replaced(&it4));
+        let diff = it3.diff(&it5);
+        it3.apply(diff).unwrap();
+        assert_eq!(it3, it5);
+    }
+
+    #[test]
+    fn test_discriminants() {
+        let mut it1 = Discriminants::A;
+        let it2 = Discriminants::B;
+        assert_eq!(it1.diff(&it2), difficient::AtomicDiff::Replaced(&it2));
+        it1.apply(it1.diff(&it2)).unwrap();
+        assert_eq!(it1 as u8, 5);
+
+        let mut it3 = ReprWithFields::A(1);
+        let it4 = ReprWithFields::A(2);
+        let it5 = ReprWithFields::B { x: 3 };
+        let it6 = ReprWithFields::C;
+        for other in [&it4, &it5, &it6] {
+            let diff = it3.diff(other);
+            it3.apply(diff).unwrap();
+            assert_eq!(&it3, other);
+        }
+    }
+
+    #[test]
+    fn test_non_exhaustive() {
+        use difficient::DiffableRemote;
+
+        let mut it1 = NonExhaustive::A;
+        let it2 = NonExhaustive::B { x: 1 };
+        let it3 = NonExhaustive::B { x: 2 };
+        it1.apply(it1.diff(&it2)).unwrap();
+        assert_eq!(it1, it2);
+        let diff = it1.diff(&it3);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it3);
+
+        let mut it4 = NonExhaustiveStruct { x: 1 };
+        let it5 = NonExhaustiveStruct { x: 2 };
+        it4.apply(it4.diff(&it5)).unwrap();
+        assert_eq!(it4, it5);
+
+        let mut kind = std::io::ErrorKind::NotFound;
+        let other = std::io::ErrorKind::TimedOut;
+        let diff = ErrorKindDef::diff(&kind, &other);
+        assert_eq!(diff, difficient::AtomicDiff::Replaced(&other));
+        ErrorKindDef::apply(&mut kind, diff).unwrap();
+        assert_eq!(kind, other);
+    }
+
+    #[test]
+    fn test_single_variant() {
+        let mut it1 = SingleVariant::Only(1);
+        let it2 = SingleVariant::Only(2);
+        it1.apply(it1.diff(&it2)).unwrap();
+        assert_eq!(it1, it2);
+    }
+
+    #[test]
+    fn test_eq_first() {
+        let it1 = Tick {
+            positions: [(1, (0, 0)), (2, (5, 5))].into_iter().collect(),
+            names: [(1, "a".to_string())].into_iter().collect(),
+        };
+        assert_eq!(it1.diff(&it1), difficient::DeepDiff::Unchanged);
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let mut it2 = it1.clone();
+        it2.positions.insert(2, (6, 5));
+        let mut it3 = it1.clone();
+        let diff = it3.diff(&it2);
+        assert!(matches!(diff, difficient::DeepDiff::Patched(_)));
+        it3.apply(diff).unwrap();
+        assert_eq!(it3, it2);
+
+        let mut cell1 = Cell::Full { tick: it1.clone() };
+        let cell2 = Cell::Full { tick: it2 };
+        assert_eq!(cell1.diff(&cell1.clone()), difficient::DeepDiff::Unchanged);
+        assert_eq!(
+            Cell::Empty.diff(&Cell::Empty),
+            difficient::DeepDiff::Unchanged
+        );
+        cell1.apply(cell1.diff(&cell2)).unwrap();
+        assert_eq!(cell1, cell2);
+    }
+
+    #[test]
+    fn test_shared_pointers() {
+        use std::{borrow::Cow, rc::Rc, sync::Arc};
+
+        let shared = Arc::new(Address {
+            street: "shared".into(),
+            city: "c1".into(),
+        });
+        let w1 = World {
+            terrain: shared.clone(),
+            local: Rc::new(Address {
+                street: "local".into(),
+                city: "c2".into(),
+            }),
+            label: Cow::Owned((0, "w".into())),
+        };
+        assert_eq!(w1.diff(&w1.clone()), difficient::DeepDiff::Unchanged);
+        assert_eq!(
+            shared.diff(&shared.clone()),
+            difficient::DeepDiff::Unchanged
+        );
+
+        let mut w2 = w1.clone();
+        Arc::make_mut(&mut w2.terrain).city = "c10".into();
+        Rc::make_mut(&mut w2.local).street = "changed".into();
+        let mut w3 = w1.clone();
+        let diff = w3.diff(&w2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert!(matches!(patch.terrain, difficient::DeepDiff::Patched(_)));
+        assert!(matches!(patch.label, difficient::DeepDiff::Unchanged));
+        w3.apply(diff).unwrap();
+        assert_eq!(w3, w2);
+        // copy-on-write leaves other holders of the pointer untouched
+        assert_eq!(shared.city, "c1");
+        assert_eq!(w1.terrain.city, "c1");
+
+        let borrowed = Address {
+            street: "cow".into(),
+            city: "c3".into(),
+        };
+        let mut c1: Cow<Address> = Cow::Borrowed(&borrowed);
+        let c2: Cow<Address> = Cow::Owned(Address {
+            street: "cow".into(),
+            city: "c4".into(),
+        });
+        assert_eq!(c1.diff(&c1.clone()), difficient::DeepDiff::Unchanged);
+        c1.apply(c1.diff(&c2)).unwrap();
+        assert_eq!(c1, c2);
+        assert_eq!(borrowed.city, "c3");
+    }
+
+    #[test]
+    fn test_std_value_types() {
+        let mut it1 = Stats {
+            count: 1,
+            total: 10,
+            initial: 'a',
+            id: std::num::NonZeroU32::new(7).unwrap(),
+            counter: std::num::Wrapping(255),
+            elapsed: std::time::Duration::from_secs(1),
+            path: "/tmp".into(),
+            host: "127.0.0.1:80".parse().unwrap(),
+            order: std::cmp::Ordering::Less,
+            window: 0..10,
+            rank: std::cmp::Reverse(1),
+            label: "first",
+            marker: std::marker::PhantomData,
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let mut it2 = it1.clone();
+        it2.count = 2;
+        it2.counter += 1;
+        it2.host = "[::1]:8080".parse().unwrap();
+        it2.window = 5..10;
+        it2.label = "other";
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(patch.count, difficient::AtomicDiff::Replaced(&2));
+        assert_eq!(patch.total, difficient::AtomicDiff::Unchanged);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // non-'static string slices
+        let owned = (String::from("short"), String::from("lived"));
+        let mut s1: &str = &owned.0;
+        let s2: &str = &owned.1;
+        assert_eq!(s1.diff(&s1), difficient::AtomicDiff::Unchanged);
+        s1.apply(s1.diff(&s2)).unwrap();
+        assert_eq!(s1, "lived");
+    }
+
+    #[test]
+    fn test_sequences() {
+        let mut it1 = Telemetry {
+            position: [1.0, 2.0, 3.0],
+            samples: [1, 2, 3, 4].into_iter().collect(),
+            tags: vec!["a".to_string()].into_boxed_slice(),
+            events: [1, 2].into_iter().collect(),
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let mut it2 = it1.clone();
+        it2.position[1] = 5.0;
+        it2.samples.pop_front();
+        it2.samples.push_back(5);
+        it2.events.push_back(3);
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        let difficient::DeepDiff::Patched(position) = &patch.position else {
+            panic!("expected an element-wise patch")
+        };
+        assert_eq!(
+            position.0.iter().collect::<Vec<_>>(),
+            [(&1, &difficient::AtomicDiff::Replaced(&5.0))]
+        );
+        assert_eq!(
+            patch.samples,
+            difficient::DeepDiff::Patched(difficient::VecDequeDiff {
+                pop_front: 1,
+                push_back: vec![&5],
+            })
+        );
+        assert_eq!(patch.tags, difficient::AtomicDiff::Unchanged);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // every element replaced
+        let arr1 = [1, 2];
+        let arr2 = [3, 4];
+        assert_eq!(arr1.diff(&arr2), difficient::DeepDiff::Replaced(&arr2));
+
+        // an index past the end of the array
+        let mut arr3 = [1, 2];
+        let bad = difficient::DeepDiff::Patched(difficient::ArrayDiff(
+            [(2, difficient::AtomicDiff::Replaced(&5))]
+                .into_iter()
+                .collect(),
+        ));
+        assert_eq!(
+            arr3.apply(bad).unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+
+        // nothing in common
+        let mut dq1: std::collections::VecDeque<_> = [1, 2, 3].into_iter().collect();
+        let dq2: std::collections::VecDeque<_> = [4, 5].into_iter().collect();
+        let diff = dq1.diff(&dq2);
+        assert_eq!(diff, difficient::DeepDiff::Replaced(&dq2));
+        dq1.apply(diff).unwrap();
+        assert_eq!(dq1, dq2);
+
+        // pure append
+        let mut dq3: std::collections::VecDeque<_> = [1, 2].into_iter().collect();
+        let dq4: std::collections::VecDeque<_> = [1, 2, 3].into_iter().collect();
+        let diff = dq3.diff(&dq4);
+        assert!(matches!(diff, difficient::DeepDiff::Patched(_)));
+        dq3.apply(diff).unwrap();
+        assert_eq!(dq3, dq4);
+
+        // repeated elements, where the first partial match is not the right one
+        let dq5: std::collections::VecDeque<_> = [1, 1, 2, 1, 1, 1].into_iter().collect();
+        let dq6: std::collections::VecDeque<_> = [1, 1, 1, 2].into_iter().collect();
+        assert_eq!(
+            dq5.diff(&dq6),
+            difficient::DeepDiff::Patched(difficient::VecDequeDiff {
+                pop_front: 3,
+                push_back: vec![&2],
+            })
+        );
+
+        // a large buffer which only differs at the end
+        let big1: std::collections::VecDeque<u32> = (0..100_000).collect();
+        let mut big2 = big1.clone();
+        *big2.back_mut().unwrap() = 0;
+        let mut big3 = big1.clone();
+        let diff = big3.diff(&big2);
+        assert!(matches!(diff, difficient::DeepDiff::Replaced(_)));
+        big3.apply(diff).unwrap();
+        assert_eq!(big3, big2);
+
+        // popping more than is there
+        let mut short: std::collections::VecDeque<u8> = std::collections::VecDeque::new();
+        let bad = difficient::DeepDiff::Patched(difficient::VecDequeDiff {
+            pop_front: 1,
+            push_back: vec![],
+        });
+        assert_eq!(
+            short.apply(bad).unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+    }
+
+    #[test]
+    fn test_result() {
+        let address = |street: &str| Address {
+            street: street.into(),
+            city: "Paris".into(),
+        };
+        let mut it1 = Cached {
+            response: Ok(address("Rue Cler")),
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let it2 = Cached {
+            response: Ok(address("Rue Mouffetard")),
+        };
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert!(matches!(
+            patch.response,
+            difficient::DeepDiff::Patched(Ok(_))
+        ));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        let it3 = Cached {
+            response: Err((404, "not found".into())),
+        };
+        let diff = it1.diff(&it3);
+        assert_eq!(diff, difficient::DeepDiff::Replaced(&it3));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it3);
+
+        let it4 = Cached {
+            response: Err((500, "not found".into())),
+        };
+        let diff = it1.diff(&it4);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert!(matches!(
+            patch.response,
+            difficient::DeepDiff::Patched(Err(_))
+        ));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it4);
+
+        // an Err patch cannot be applied to an Ok value
+        let mut ok = it2.clone();
+        assert_eq!(
+            ok.apply(it3.diff(&it4)).unwrap_err(),
+            [difficient::ApplyError::MismatchingEnum]
+        );
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn test_text() {
+        use difficient::text::TextEdit;
+
+        let mut it1 = Note {
+            body: "one\ntwo\nthree\n".into(),
+            title: "Shopping list".into(),
+            emoji: "🇫🇷🇩🇪".into(),
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let it2 = Note {
+            body: "one\n2\nthree\n".into(),
+            title: "Shopping basket".into(),
+            emoji: "🇫🇷🇮🇹".into(),
+        };
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(
+            patch.body,
+            difficient::DeepDiff::Patched(difficient::text::TextDiff(vec![
+                TextEdit::Keep(4),
+                TextEdit::Delete(4),
+                TextEdit::Insert("2\n"),
+                TextEdit::Keep(6),
+            ]))
+        );
+        assert_eq!(
+            patch.title,
+            difficient::DeepDiff::Patched(difficient::text::TextDiff(vec![
+                TextEdit::Keep(9),
+                TextEdit::Delete(4),
+                TextEdit::Insert("basket"),
+            ]))
+        );
+        // flags are single graphemes made of two code points
+        assert_eq!(
+            patch.emoji,
+            difficient::DeepDiff::Patched(difficient::text::TextDiff(vec![
+                TextEdit::Keep(8),
+                TextEdit::Delete(8),
+                TextEdit::Insert("🇮🇹"),
+            ]))
+        );
+        assert_eq!(
+            patch.body.unified_diff(&it1.body).unwrap(),
+            "@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // nothing in common
+        let mut s1 = String::from("abc");
+        let s2 = String::from("xyz");
+        let diff = <difficient::text::Graphemes as difficient::DiffableRemote>::diff(&s1, &s2);
+        assert_eq!(diff, difficient::DeepDiff::Replaced(&s2));
+
+        // the diff does not fit the text it is applied to
+        let truncated = String::from("one\n2\n");
+        let diff =
+            <difficient::text::Lines as difficient::DiffableRemote>::diff(&it2.body, &truncated);
+        assert_eq!(
+            <difficient::text::Lines as difficient::DiffableRemote>::apply(&mut s1, diff)
+                .unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+        assert_eq!(s1, "abc");
+    }
+
+    #[test]
+    fn test_byte_delta() {
+        use difficient::delta::{ByteDelta, ByteEdit, Delta};
+        use difficient::DiffableRemote;
+
+        // something that doesn't repeat itself
+        let noise = |len: usize, seed: u64| -> Vec<u8> {
+            let mut x = seed;
+            (0..len)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    x as u8
+                })
+                .collect()
+        };
+
+        let base = noise(100_000, 1);
+        let mut edited = base.clone();
+        edited[50_000] ^= 0xff;
+        edited.splice(70_000..70_010, *b"inserted!");
+        edited.extend_from_slice(b"tail");
+
+        let diff = Delta::diff(&base, &edited);
+        let difficient::DeepDiff::Patched(delta) = &diff else {
+            panic!("expected a patch")
+        };
+        let inserted: usize = delta
+            .0
+            .iter()
+            .map(|edit| match edit {
+                ByteEdit::Insert(bytes) => bytes.len(),
+                ByteEdit::Copy { .. } => 0,
+            })
+            .sum();
+        assert!(inserted < 20, "{delta:?}");
+        let mut patched = base.clone();
+        Delta::apply(&mut patched, diff).unwrap();
+        assert_eq!(patched, edited);
+
+        // a pure prefix keeps everything
+        let prefixed = [b"header".as_slice(), &base].concat();
+        assert_eq!(
+            Delta::diff(&base, &prefixed),
+            difficient::DeepDiff::Patched(ByteDelta::new(vec![
+                ByteEdit::Insert(b"header"),
+                ByteEdit::Copy {
+                    offset: 0,
+                    len: base.len()
+                },
+            ]))
+        );
+
+        // nothing in common
+        let other = noise(1000, 2);
+        assert_eq!(
+            Delta::diff(&base, &other),
+            difficient::DeepDiff::Replaced(&other)
+        );
+
+        // copying past the end of the base
+        let mut short = vec![1u8, 2, 3];
+        let bad = difficient::DeepDiff::Patched(ByteDelta::new(vec![ByteEdit::Copy {
+            offset: 2,
+            len: 2,
+        }]));
+        assert_eq!(
+            Delta::apply(&mut short, bad).unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+        assert_eq!(short, [1, 2, 3]);
+
+        let mut it1 = Attachment {
+            data: base.clone(),
+            thumbnail: noise(64, 3).into_boxed_slice(),
+        };
+        let it2 = Attachment {
+            data: edited,
+            thumbnail: it1.thumbnail.clone(),
+        };
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert!(matches!(patch.data, difficient::DeepDiff::Patched(_)));
+        assert_eq!(patch.thumbnail, difficient::DeepDiff::Unchanged);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes() {
+        let mut it1 = Frame {
+            raw: bytes::Bytes::from_static(b"raw"),
+            payload: bytes::Bytes::from_static(b"a fairly long payload, long enough for a delta"),
+        };
+        let it2 = Frame {
+            raw: bytes::Bytes::from_static(b"raw!"),
+            payload: bytes::Bytes::from_static(b"a fairly long payload, long enough for deltas"),
+        };
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(patch.raw, difficient::AtomicDiff::Replaced(&it2.raw));
+        assert!(matches!(patch.payload, difficient::DeepDiff::Patched(_)));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+    }
+
+    #[test]
+    fn test_counters() {
+        use difficient::counter::{Counter, CounterDiff};
+        use std::num::Wrapping;
+
+        let base = Metrics {
+            hits: Counter(10),
+            balance: Counter(0),
+            sequence: Counter(Wrapping(250)),
+            load: Counter(1.5),
+        };
+        assert_eq!(base.diff(&base.clone()), difficient::DeepDiff::Unchanged);
+
+        let mine = Metrics {
+            hits: Counter(11),
+            balance: Counter(-5),
+            sequence: Counter(Wrapping(4)),
+            load: Counter(1.0),
+        };
+        let diff = base.diff(&mine);
+        assert_eq!(
+            diff,
+            difficient::DeepDiff::Patched(MetricsDiff {
+                hits: difficient::DeepDiff::Patched(CounterDiff::Increment(1)),
+                balance: difficient::DeepDiff::Patched(CounterDiff::Decrement(5)),
+                sequence: difficient::DeepDiff::Patched(CounterDiff::Increment(Wrapping(10))),
+                load: difficient::DeepDiff::Patched(CounterDiff::Decrement(0.5)),
+            })
+        );
+
+        // another replica has moved on concurrently; both changes survive
+        let mut theirs = Metrics {
+            hits: Counter(12),
+            balance: Counter(100),
+            sequence: Counter(Wrapping(255)),
+            load: Counter(2.0),
+        };
+        theirs.apply(diff).unwrap();
+        assert_eq!(
+            theirs,
+            Metrics {
+                hits: Counter(13),
+                balance: Counter(95),
+                sequence: Counter(Wrapping(9)),
+                load: Counter(1.5),
+            }
+        );
+
+        // checked counters report overflow and are left alone
+        let mut full = Counter(250u8);
+        assert_eq!(
+            full.apply(Counter(0u8).diff(&Counter(10))).unwrap_err(),
+            [difficient::ApplyError::Overflow]
+        );
+        assert_eq!(full, Counter(250));
+
+        // an unrepresentable delta falls back to replacement
+        let max = Counter(i64::MAX);
+        assert_eq!(
+            Counter(i64::MIN).diff(&max),
+            difficient::DeepDiff::Replaced(&max)
+        );
+    }
+
+    #[test]
+    fn test_enum_tolerance() {
+        let mut it1 = Reading::Value(1.0);
+        let it2 = Reading::Value(1.05);
+        // eq_first must not short-circuit past the tolerance check
+        assert_eq!(it1.diff(&it2), difficient::DeepDiff::Unchanged);
+        let it3 = Reading::Value(1.5);
+        let diff = it1.diff(&it3);
+        assert_ne!(diff, difficient::DeepDiff::Unchanged);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it3);
+
+        let it1 = Reading::Pair { a: 0.1 + 0.2, b: 1 };
+        let it2 = Reading::Pair { a: 0.3, b: 1 };
+        assert_eq!(it1.diff(&it2), difficient::DeepDiff::Unchanged);
+        let it3 = Reading::Pair { a: 0.3, b: 2 };
+        assert_ne!(it1.diff(&it3), difficient::DeepDiff::Unchanged);
+        assert_ne!(Reading::Empty.diff(&it3), difficient::DeepDiff::Unchanged);
+        assert_eq!(
+            Reading::Empty.diff(&Reading::Empty),
+            difficient::DeepDiff::Unchanged
+        );
+    }
+
+    #[test]
+    fn test_float_tolerance() {
+        let mut it1 = Body {
+            x: 1.0,
+            mass: 1000.0,
+            spin: 0.1 + 0.2,
+            charge: 0.0,
+            temperature: f64::NAN,
+        };
+        let jittered = Body {
+            x: 1.0 + 1e-9,
+            mass: 1000.5,
+            spin: 0.3,
+            charge: 1e-300,
+            temperature: f64::NAN,
+        };
+        assert_eq!(it1.diff(&jittered), difficient::DeepDiff::Unchanged);
+        // the mask compares within the tolerances, and with PartialEq otherwise,
+        // under which NaN is never equal
+        assert_eq!(it1.changed_mask(&jittered), BodyMask::TEMPERATURE);
+
+        let moved = Body {
+            x: 1.1,
+            mass: 1002.0,
+            spin: 0.3 + 1e-12,
+            charge: 1e-3,
+            temperature: 20.0,
+        };
+        let diff = it1.diff(&moved);
+        assert_eq!(diff, difficient::DeepDiff::Replaced(&moved));
+        assert_eq!(it1.changed_mask(&moved), BodyMask::all());
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, moved);
+
+        let mut it2 = it1.clone();
+        it2.mass = 2000.0;
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(patch.x, difficient::AtomicDiff::Unchanged);
+        assert_eq!(patch.mass, difficient::AtomicDiff::Replaced(&2000.0));
+
+        // a relative tolerance doesn't reach infinity
+        let mut it3 = it1.clone();
+        it3.mass = f32::INFINITY;
+        let diff = it1.diff(&it3);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(patch.mass, difficient::AtomicDiff::Replaced(&f32::INFINITY));
+        assert_eq!(it1.changed_mask(&it3), BodyMask::MASS);
+
+        let tolerance = difficient::tolerance::Tolerance {
+            ulps: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            tolerance.diff(&0.0f64, &-0.0),
+            difficient::AtomicDiff::Unchanged
+        );
+        // infinities are only equal to themselves, however close
+        assert_eq!(
+            tolerance.diff(&f64::MAX, &f64::INFINITY),
+            difficient::AtomicDiff::Replaced(&f64::INFINITY)
+        );
+        assert_eq!(
+            tolerance.diff(&f64::INFINITY, &f64::INFINITY),
+            difficient::AtomicDiff::Unchanged
+        );
+        let relative = difficient::tolerance::Tolerance {
+            relative: 1e-6,
+            ..Default::default()
+        };
+        assert_eq!(
+            relative.diff(&5.0f64, &f64::INFINITY),
+            difficient::AtomicDiff::Replaced(&f64::INFINITY)
+        );
+        assert_eq!(
+            relative.diff(&f64::NEG_INFINITY, &5.0f64),
+            difficient::AtomicDiff::Replaced(&5.0)
+        );
+        assert!(matches!(
+            tolerance.diff(&1.0f64, &f64::NAN),
+            difficient::AtomicDiff::Replaced(_)
+        ));
+    }
+
+    #[test]
+    fn test_positional() {
+        let row = |ix: usize| Address {
+            street: format!("{ix} Main Street"),
+            city: "Springfield".into(),
+        };
+        let mut it1 = Table {
+            rows: (0..1000).map(row).collect(),
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let mut it2 = it1.clone();
+        it2.rows[500].city = "Shelbyville".into();
+        let diff = it1.diff(&it2);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        let difficient::DeepDiff::Patched(rows) = &patch.rows else {
+            panic!("expected a positional patch")
+        };
+        assert_eq!(rows.0.keys().collect::<Vec<_>>(), [&500]);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // lengths differ
+        let mut it3 = it2.clone();
+        it3.rows.pop();
+        assert_eq!(it2.diff(&it3), difficient::DeepDiff::Replaced(&it3));
+
+        // the patch reaches past the end
+        let mut short = vec![row(0)];
+        let bad = difficient::DeepDiff::Patched(difficient::PositionalDiff(
+            [(3, difficient::DeepDiff::Replaced(&it2.rows[0]))]
+                .into_iter()
+                .collect(),
+        ));
+        assert_eq!(
+            <difficient::Positional<Vec<Address>> as difficient::DiffableRemote>::apply(
+                &mut short, bad
+            )
+            .unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+    }
+
+    #[test]
+    fn test_vec_append_truncate() {
+        use difficient::{DeepDiff, VecDiff};
+
+        let event = |ix: usize| format!("event {ix}");
+        let mut it1 = EventLog {
+            events: (0..100).map(event).collect(),
+        };
+        let mut it2 = it1.clone();
+        it2.events.extend((100..102).map(event));
+        let diff = it1.diff(&it2);
+        assert_eq!(
+            diff,
+            DeepDiff::Patched(EventLogDiff {
+                events: DeepDiff::Patched(VecDiff::Appended(&it2.events[100..])),
+            })
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        let mut it3 = it2.clone();
+        it3.events.truncate(10);
+        let diff = it1.diff(&it3);
+        assert_eq!(
+            diff,
+            DeepDiff::Patched(EventLogDiff {
+                events: DeepDiff::Patched(VecDiff::Truncated(10)),
+            })
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it3);
+
+        // same length, different contents
+        let mut it4 = it3.clone();
+        it4.events[0] = "rewritten".into();
+        assert_eq!(it3.diff(&it4), DeepDiff::Replaced(&it4));
+
+        // diverged before growing
+        let mut it5 = it4.clone();
+        it5.events.push(event(10));
+        assert_eq!(it3.diff(&it5), DeepDiff::Replaced(&it5));
+
+        // truncating past the end
+        let mut short = vec![1, 2];
+        assert_eq!(
+            short
+                .apply(DeepDiff::Patched(VecDiff::Truncated(3)))
+                .unwrap_err(),
+            [difficient::ApplyError::MismatchingBase]
+        );
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn test_pod_chunks() {
+        use difficient::pod::ChangedRanges;
+
+        let mut it1 = Simulation {
+            positions: (0..1_000_000).map(|ix| ix as f32).collect(),
+            pixels: vec![[0, 0, 0, 255]; 10_000],
+        };
+        assert_eq!(it1.diff(&it1.clone()), difficient::DeepDiff::Unchanged);
+
+        let mut it2 = it1.clone();
+        it2.positions[10] = -1.0;
+        it2.positions[11] = -1.0;
+        it2.positions[999_999] = -1.0;
+        it2.pixels[5000..5100].fill([255, 0, 0, 255]);
+        let diff = it1.diff(&it2);
+        assert_eq!(
+            diff,
+            difficient::DeepDiff::Patched(SimulationDiff {
+                positions: difficient::DeepDiff::Patched(ChangedRanges(vec![
+                    (10..12, &it2.positions[10..12]),
+                    (999_999..1_000_000, &it2.positions[999_999..]),
+                ])),
+                pixels: difficient::DeepDiff::Patched(ChangedRanges(vec![(
+                    5000..5100,
+                    &it2.pixels[5000..5100]
+                )])),
+            })
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // a change spanning several chunks is one range
+        let mut it3 = it2.clone();
+        it3.positions[1000..20_000].fill(0.5);
+        let diff = it2.diff(&it3);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        let difficient::DeepDiff::Patched(ranges) = &patch.positions else {
+            panic!("expected changed ranges")
+        };
+        assert_eq!(ranges.0.len(), 1);
+        assert_eq!(ranges.0[0].0, 1000..20_000);
+
+        // lengths differ
+        let mut it4 = it3.clone();
+        it4.pixels.pop();
+        let diff = it3.diff(&it4);
+        let difficient::DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        assert_eq!(patch.pixels, difficient::DeepDiff::Replaced(&it4.pixels));
+    }
+
+    #[test]
+    fn test_map_insertions() {
+        let mut it1: HashMap<u32, String> = [(1, "one".to_string())].into_iter().collect();
+        let mut it2 = it1.clone();
+        it2.insert(2, "two".into());
+        let diff = it1.diff(&it2);
+        assert_eq!(
+            diff,
+            difficient::DeepDiff::Patched(
+                [(2, difficient::KvDiff::Inserted(&it2[&2]))]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        use difficient::json::ValueDiff;
+        use difficient::{DeepDiff, KvDiff, VecDiff};
+        use serde_json::json;
+
+        let mut it1 = Settings {
+            name: "app".into(),
+            extra: json!({
+                "theme": "dark",
+                "columns": ["id", "name", "email"],
+                "limits": { "rows": 100, "cols": 10 },
+                "beta": true,
+            }),
+        };
+        assert_eq!(it1.diff(&it1.clone()), DeepDiff::Unchanged);
+
+        let it2 = Settings {
+            name: "app".into(),
+            extra: json!({
+                "theme": "dark",
+                "columns": ["id", "full name", "email", "phone"],
+                "limits": { "rows": 200, "cols": 10 },
+                "locale": "fr",
+            }),
+        };
+        let diff = it1.diff(&it2);
+        let DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        let expect = DeepDiff::Patched(ValueDiff::Object(
+            [
+                (
+                    "columns".to_string(),
+                    KvDiff::Diff(DeepDiff::Patched(ValueDiff::Array {
+                        elems: [(1, DeepDiff::Replaced(&it2.extra["columns"][1]))]
+                            .into_iter()
+                            .collect(),
+                        resize: Some(VecDiff::Appended(
+                            &it2.extra["columns"].as_array().unwrap()[3..],
+                        )),
+                    })),
+                ),
+                (
+                    "limits".to_string(),
+                    KvDiff::Diff(DeepDiff::Patched(ValueDiff::Object(
+                        [(
+                            "rows".to_string(),
+                            KvDiff::Diff(DeepDiff::Replaced(&it2.extra["limits"]["rows"])),
+                        )]
+                        .into_iter()
+                        .collect(),
+                    ))),
+                ),
+                ("beta".to_string(), KvDiff::Removed),
+                ("locale".to_string(), KvDiff::Inserted(&it2.extra["locale"])),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+        assert_eq!(patch.extra, expect);
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+
+        // a change of kind is a replacement
+        let string = json!("10");
+        let number = json!(10);
+        assert_eq!(string.diff(&number), DeepDiff::Replaced(&number));
+
+        // truncation
+        let mut long = json!([1, 2, 3]);
+        let short = json!([1, 2]);
+        let diff = long.diff(&short);
+        assert_eq!(
+            diff,
+            DeepDiff::Patched(ValueDiff::Array {
+                elems: Default::default(),
+                resize: Some(VecDiff::Truncated(2)),
+            })
+        );
+        long.apply(diff).unwrap();
+        assert_eq!(long, short);
+
+        // an object patch does not apply to an array
+        let mut array = json!([]);
+        let target = json!({ "a": 1, "b": 2 });
+        let patch = json!({ "a": 1 }).diff(&target);
+        assert_eq!(
+            array.apply(patch).unwrap_err(),
+            [difficient::ApplyError::MismatchingEnum]
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_serde_diff() {
+        use difficient::{apply_serde, serde_diff, DynChange, PathSegment};
+        use serde_json::json;
+
+        let mut it1 = Legacy {
+            id: 1,
+            owner: None,
+            scores: [("alice".to_string(), vec![1, 2])].into_iter().collect(),
+            mode: LegacyMode::On { level: 1 },
+        };
+        assert!(serde_diff(&it1, &it1.clone()).unwrap().is_empty());
+
+        let it2 = Legacy {
+            id: 1,
+            owner: Some("bob".into()),
+            scores: [
+                ("alice".to_string(), vec![1, 2, 3]),
+                ("bob".to_string(), vec![]),
+            ]
+            .into_iter()
+            .collect(),
+            mode: LegacyMode::On { level: 2 },
+        };
+        let diff = serde_diff(&it1, &it2).unwrap();
+        let key = |k: &str| PathSegment::Key(k.into());
+        assert_eq!(
+            diff.0,
+            [
+                DynChange::Set {
+                    path: vec![key("mode")],
+                    value: json!({ "On": { "level": 2 } }),
+                },
+                DynChange::Set {
+                    path: vec![key("owner")],
+                    value: json!("bob"),
+                },
+                DynChange::Append {
+                    path: vec![key("scores"), key("alice")],
+                    values: vec![json!(3)],
+                },
+                DynChange::Set {
+                    path: vec![key("scores"), key("bob")],
+                    value: json!([]),
+                },
+            ]
+        );
+        apply_serde(&mut it1, &diff).unwrap();
+        assert_eq!(it1, it2);
+
+        let it3 = Legacy {
+            mode: LegacyMode::Off,
+            ..it2.clone()
+        };
+        let diff = serde_diff(&it1, &it3).unwrap();
+        apply_serde(&mut it1, &diff).unwrap();
+        assert_eq!(it1, it3);
+
+        // the removed key is not there
+        let mut it4 = it3.clone();
+        it4.scores.remove("bob");
+        let diff = serde_diff(&it3, &it4).unwrap();
+        let mut it5 = it4.clone();
+        assert!(matches!(
+            apply_serde(&mut it5, &diff),
+            Err(difficient::SerdeDiffError::Apply(errs)) if errs == [difficient::ApplyError::MissingKey]
+        ));
+        assert_eq!(it5, it4);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_indexmap() {
+        use difficient::{indexmap::IndexMapDiff, DeepDiff, KvDiff};
+        use indexmap::{IndexMap, IndexSet};
+
+        let columns = |cols: &[(&str, u32)]| -> IndexMap<String, u32> {
+            cols.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+        };
+        let hidden = |elems: &[&str]| -> IndexSet<String> {
+            elems.iter().map(|elem| elem.to_string()).collect()
+        };
+        let mut it1 = Grid {
+            columns: columns(&[("id", 50), ("name", 200), ("email", 200), ("phone", 100)]),
+            hidden: hidden(&["a", "b", "c"]),
+        };
+        assert_eq!(it1.diff(&it1.clone()), DeepDiff::Unchanged);
+
+        // moving one column is a single move
+        let it2 = Grid {
+            columns: columns(&[("id", 50), ("phone", 100), ("name", 200), ("email", 200)]),
+            ..it1.clone()
+        };
+        let diff = it1.diff(&it2);
+        assert_eq!(
+            diff,
+            DeepDiff::Patched(GridDiff {
+                columns: DeepDiff::Patched(IndexMapDiff {
+                    entries: IndexMap::new(),
+                    moves: vec![(3, 1)],
+                }),
+                hidden: DeepDiff::Unchanged,
+            })
+        );
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it2);
+        assert!(it1.columns.keys().eq(it2.columns.keys()));
+
+        // changes, removals and insertions land in the right place
+        let it3 = Grid {
+            columns: columns(&[("notes", 300), ("id", 60), ("email", 200), ("name", 200)]),
+            hidden: hidden(&["d", "c", "a"]),
+        };
+        let diff = it1.diff(&it3);
+        let DeepDiff::Patched(patch) = &diff else {
+            panic!("expected a patch")
+        };
+        let DeepDiff::Patched(cols) = &patch.columns else {
+            panic!("expected a patch")
+        };
+        assert_eq!(
+            cols.entries["id"],
+            KvDiff::Diff(difficient::AtomicDiff::Replaced(&60))
+        );
+        assert_eq!(cols.entries["phone"], KvDiff::Removed);
+        assert_eq!(cols.entries["notes"], KvDiff::Inserted(&300));
+        it1.apply(diff).unwrap();
+        assert_eq!(it1, it3);
+        assert!(it1.columns.keys().eq(it3.columns.keys()));
+        assert!(it1.hidden.iter().eq(it3.hidden.iter()));
+
+        // every reordering of a few keys is reproduced exactly
+        let keys = ["a", "b", "c", "d", "e", "f"];
+        let mut seed = 1u64;
+        for _ in 0..500 {
+            let mut shuffled = keys.to_vec();
+            for ix in (1..shuffled.len()).rev() {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                shuffled.swap(ix, seed as usize % (ix + 1));
+            }
+            let mut base = hidden(&keys);
+            let target = hidden(&shuffled);
+            let diff = base.diff(&target);
+            base.apply(diff).unwrap();
+            assert!(base.iter().eq(target.iter()), "{shuffled:?}");
+        }
+
+        // nothing in common
+        let set1 = hidden(&["a"]);
+        let set2 = hidden(&["b"]);
+        assert_eq!(set1.diff(&set2), DeepDiff::Replaced(&set2));
+    }
 }